@@ -0,0 +1,145 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, VecDeque};
+use chrono::{DateTime, Duration, Utc};
+use crate::domain::transaction::Transaction;
+
+/// Node-local rules for when to author a block. The limits on what a block may contain are
+/// consensus rules and live in the chain spec.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuthoringPolicy {
+    /// A block is authored once this long has passed since our tip, full or not.
+    pub block_interval: Duration,
+    /// Whether a block with no transactions is authored when the interval passes.
+    pub empty_blocks: bool,
+    /// Address our coinbase pays. Without one the reward is left unclaimed.
+    pub beneficiary: Option<String>,
+}
+
+impl Default for AuthoringPolicy {
+    fn default() -> Self {
+        AuthoringPolicy {
+            block_interval: Duration::seconds(10),
+            empty_blocks: false,
+            beneficiary: None,
+        }
+    }
+}
+
+impl AuthoringPolicy {
+    /// Time left until the next block is due: right away once `ready` transactions fill a block,
+    /// otherwise `block_interval` after the tip. `None` when there is nothing to author.
+    pub fn time_until_block(&self, ready: usize, max_block_transactions: usize, tip_time: DateTime<Utc>, now: DateTime<Utc>) -> Option<Duration> {
+        if ready >= max_block_transactions {
+            return Some(Duration::zero());
+        }
+        if ready == 0 && !self.empty_blocks {
+            return None;
+        }
+        Some((tip_time + self.block_interval - now).max(Duration::zero()))
+    }
+}
+
+/// Picks the transactions for the next block out of `ready`, which holds each sender's
+/// transactions in nonce order. The pool is drained by priority (see `compare_priority`): the best
+/// next transaction of any sender goes first. A sender whose next transaction no longer fits in `max_bytes` is skipped
+/// together with its later nonces, which could not execute without it.
+pub fn select_transactions(ready: Vec<Transaction>, max_transactions: usize, max_bytes: usize) -> Vec<Transaction> {
+    let mut queues: BTreeMap<String, VecDeque<Transaction>> = BTreeMap::new();
+    for transaction in ready {
+        queues.entry(transaction.sender.clone()).or_default().push_back(transaction);
+    }
+
+    let mut selected = vec![];
+    let mut bytes = 0;
+    while selected.len() < max_transactions {
+        let Some(sender) = queues.iter()
+            .filter_map(|(sender, queue)| queue.front().map(|transaction| (sender, transaction)))
+            .min_by(|(_, a), (_, b)| compare_priority(a, b))
+            .map(|(sender, _)| sender.clone()) else { break; };
+
+        let queue = queues.get_mut(&sender).unwrap();
+        let size = queue.front().unwrap().encoded_len();
+        if bytes + size > max_bytes {
+            queues.remove(&sender);
+            continue;
+        }
+        bytes += size;
+        selected.push(queue.pop_front().unwrap());
+    }
+    selected
+}
+
+/// `Less` means `a` goes first: the higher fee per byte, then the lower hash. The signed
+/// timestamp is left out on purpose, as the sender picks it and could backdate it to jump ahead.
+pub fn compare_priority(a: &Transaction, b: &Transaction) -> Ordering {
+    // a.fee / a.size > b.fee / b.size, compared without division.
+    let a_rate = a.fee.0 as u128 * b.encoded_len() as u128;
+    let b_rate = b.fee.0 as u128 * a.encoded_len() as u128;
+    b_rate.cmp(&a_rate).then_with(|| a.hash.cmp(&b.hash))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::account;
+    use crate::domain::transaction::tests::dev_transfer;
+
+    #[test]
+    fn test_time_until_block() {
+        let policy = AuthoringPolicy::default();
+        let tip = Utc::now();
+        assert_eq!(policy.time_until_block(5, 5, tip, tip), Some(Duration::zero()));
+        assert_eq!(policy.time_until_block(0, 5, tip, tip), None);
+        assert_eq!(policy.time_until_block(1, 5, tip, tip + Duration::seconds(4)), Some(Duration::seconds(6)));
+        assert_eq!(policy.time_until_block(1, 5, tip, tip + Duration::seconds(60)), Some(Duration::zero()));
+
+        let policy = AuthoringPolicy { empty_blocks: true, ..AuthoringPolicy::default() };
+        assert_eq!(policy.time_until_block(0, 5, tip, tip), Some(Duration::seconds(10)));
+    }
+
+    #[test]
+    fn test_select_transactions_by_priority() {
+        let ready = vec![
+            dev_transfer("Alice", 0, "Bob", 1, 0),
+            dev_transfer("Carol", 0, "Bob", 1, 0),
+            dev_transfer("Alice", 1, "Bob", 1, 0),
+        ];
+        let selected = select_transactions(ready.clone(), 3, usize::MAX);
+        // Equal fees: the lower hash goes first, but never ahead of its sender's earlier nonces.
+        assert_eq!(selected[0].hash, ready[0].hash.clone().min(ready[1].hash.clone()));
+        let alice = account::dev_address("Alice");
+        let nonces: Vec<u64> = selected.iter().filter(|transaction| transaction.sender == alice).map(|transaction| transaction.nonce).collect();
+        assert_eq!(nonces, vec![0, 1]);
+    }
+
+    #[test]
+    fn test_backdated_timestamp_does_not_jump_ahead() {
+        let alice = dev_transfer("Alice", 0, "Bob", 1, 0);
+        let mut carol = dev_transfer("Carol", 0, "Bob", 1, 0);
+        carol.timestamp = Some("2000-01-01T00:00:00+00:00".to_string());
+        carol.sign(&account::dev_key("Carol"));
+        assert_eq!(compare_priority(&alice, &carol), alice.hash.cmp(&carol.hash));
+    }
+
+    #[test]
+    fn test_select_transactions_by_fee_per_byte() {
+        let ready = vec![
+            dev_transfer("Alice", 0, "Bob", 1, 1),
+            dev_transfer("Alice", 1, "Bob", 1, 9),
+            dev_transfer("Carol", 0, "Bob", 1, 5),
+        ];
+        let selected = select_transactions(ready.clone(), 2, usize::MAX);
+        let hashes: Vec<&String> = selected.iter().map(|transaction| &transaction.hash).collect();
+        // Alice's high fee cannot jump ahead of her own nonce 0.
+        assert_eq!(hashes, vec![&ready[2].hash, &ready[0].hash]);
+    }
+
+    #[test]
+    fn test_select_transactions_respects_byte_limit() {
+        let ready = vec![dev_transfer("Alice", 0, "Bob", 1, 0), dev_transfer("Alice", 1, "Bob", 1, 0)];
+        let one = ready[0].encoded_len();
+        let selected = select_transactions(ready.clone(), 10, one + 1);
+        assert_eq!(selected.len(), 1);
+        assert!(select_transactions(ready, 10, one - 1).is_empty());
+    }
+}
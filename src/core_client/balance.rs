@@ -1,40 +1,59 @@
-use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+use crate::core_client::chain_spec::ChainSpec;
+use crate::core_client::overlay::{EventLog, StorageMap, StorageValue, Transactional};
+use crate::core_client::pallet::{self, RuntimeEvent};
+use crate::domain::call::RuntimeCall;
 use crate::wasm::call::WasmCall;
+use crate::domain::amount::Amount;
 
-#[derive(Debug)]
+pub use crate::domain::call::BalancesCall as Call;
+
+/// Events deposited by the balances pallet.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Event {
+    Transfer { from: String, to: String, amount: Amount },
+    Minted { to: String, amount: Amount },
+    Burned { from: String, amount: Amount },
+}
+
+#[derive(Debug, Clone)]
 pub struct Pallet {
-    pub balance: BTreeMap<String, u64>,
+    pub balance: StorageMap<String, Amount>,
+    /// Sum of all balances: genesis allocations plus everything minted since.
+    pub total_issuance: StorageValue<Amount>,
+    events: EventLog<Event>,
 }
 
 impl Pallet {
     pub fn new() -> Self {
         Pallet {
-            balance: BTreeMap::new(),
+            balance: StorageMap::new(),
+            total_issuance: StorageValue::new(Amount::ZERO),
+            events: EventLog::new(),
         }
     }
 
-    pub fn balance(&self, address: &str) -> u64 {
+    pub fn balance(&self, address: &str) -> Amount {
         match self.balance.get(address) {
             Some(balance) => *balance,
-            None => 0,
+            None => Amount::ZERO,
         }
     }
 
-    pub fn transfer(&mut self, sender: &str, to: &str, amount: u64) -> Result<(), String> {
-        if amount == 0 { return Err("Amount must be greater than 0".to_string()); }
+    pub fn transfer(&mut self, sender: &str, to: &str, amount: Amount) -> Result<(), String> {
+        if amount.is_zero() { return Err("Amount must be greater than 0".to_string()); }
         if self.balance(sender) < amount { return Err("Insufficient balance".to_string()); }
 
-        let sub_result = self.sub_balance(sender, amount);
-        if sub_result.is_err() { return Err(sub_result.unwrap_err()); }
-        let add_result = self.add_balance(to, amount);
-        if add_result.is_err() {
-            self.add_balance(sender, amount);
-            return Err(add_result.unwrap_err());
+        self.sub_balance(sender, amount)?;
+        if let Err(e) = self.add_balance(to, amount) {
+            self.add_balance(sender, amount)?;
+            return Err(e);
         }
+        self.events.deposit(Event::Transfer { from: sender.to_string(), to: to.to_string(), amount });
         Ok(())
     }
 
-    fn add_balance(&mut self, address: &str, amount: u64) -> Result<(), String> {
+    fn add_balance(&mut self, address: &str, amount: Amount) -> Result<(), String> {
         let balance = self.balance(address);
         let new_balance = balance.checked_add(amount);
         if new_balance.is_none() { return Err("Not possible add balance".to_string()); }
@@ -45,13 +64,13 @@ impl Pallet {
         let call = store.data().clone();
 
         // Call the add function of WasmCall
-        let result = call.add(&mut store, balance as u32, amount as u32);
+        let result = call.add(&mut store, balance.0 as u32, amount.0 as u32);
         println!("Result from WasmCall add: {}", result);
 
         self.balance.insert(address.to_string(), new_balance.unwrap());
         Ok(())
     }
-    fn sub_balance(&mut self, address: &str, amount: u64) -> Result<(), String> {
+    fn sub_balance(&mut self, address: &str, amount: Amount) -> Result<(), String> {
         let balance = self.balance(address);
         let new_balance = balance.checked_sub(amount);
         if new_balance.is_none() { return Err("Not possible sub balance".to_string()); }
@@ -59,14 +78,88 @@ impl Pallet {
         Ok(())
     }
 
-    pub fn set_balance(&mut self, address: &str, amount: u64) -> Result<(), String> {
+    pub fn set_balance(&mut self, address: &str, amount: Amount) -> Result<(), String> {
+        let total_issuance = self.total_issuance.checked_sub(self.balance(address))
+            .and_then(|total| total.checked_add(amount))
+            .ok_or("Total issuance overflow")?;
         self.balance.insert(address.to_string(), amount);
+        self.total_issuance.set(total_issuance);
+        Ok(())
+    }
+
+    /// Destroys `amount` of `address`'s coins, e.g. a fee nobody collects.
+    pub fn burn(&mut self, address: &str, amount: Amount) -> Result<(), String> {
+        self.sub_balance(address, amount)?;
+        self.total_issuance.set(self.total_issuance.checked_sub(amount).unwrap_or(Amount::ZERO));
+        self.events.deposit(Event::Burned { from: address.to_string(), amount });
+        Ok(())
+    }
+
+    /// Creates `amount` new coins in `address`, e.g. a block reward.
+    pub fn mint(&mut self, address: &str, amount: Amount) -> Result<(), String> {
+        let total_issuance = self.total_issuance.checked_add(amount).ok_or("Total issuance overflow")?;
+        self.add_balance(address, amount)?;
+        self.total_issuance.set(total_issuance);
+        self.events.deposit(Event::Minted { to: address.to_string(), amount });
         Ok(())
     }
 }
 
-#[cfg(test)]
+impl pallet::Pallet for Pallet {
+    fn name(&self) -> &'static str {
+        "Balances"
+    }
+
+    fn genesis(&mut self, spec: &ChainSpec) -> Result<(), String> {
+        for (address, amount) in &spec.balances {
+            self.set_balance(address, *amount)?;
+        }
+        Ok(())
+    }
+
+    fn dispatch(&mut self, origin: &str, call: &RuntimeCall) -> Result<(), String> {
+        match call {
+            RuntimeCall::Balances(Call::Transfer { to, amount }) => self.transfer(origin, to, *amount),
+            _ => Err(format!("Balances cannot dispatch {:?}", call)),
+        }
+    }
 
+    fn storage(&self) -> Vec<(String, String)> {
+        let mut storage: Vec<(String, String)> = self.balance.iter()
+            .map(|(address, amount)| (format!("balance/{}", address), amount.to_string()))
+            .collect();
+        storage.push(("total_issuance".to_string(), self.total_issuance.to_string()));
+        storage
+    }
+
+    fn restore(&mut self, storage: &[(String, String)]) -> Result<(), String> {
+        let mut restored = Pallet::new();
+        for (key, value) in storage {
+            let amount = value.parse().map(Amount).map_err(|_| format!("Invalid Balances storage value {}", value))?;
+            match key.strip_prefix("balance/") {
+                Some(address) => restored.balance.insert(address.to_string(), amount),
+                None if key == "total_issuance" => restored.total_issuance.set(amount),
+                None => return Err(format!("Unknown Balances storage item {}", key)),
+            }
+        }
+        *self = restored;
+        Ok(())
+    }
+
+    fn take_events(&mut self) -> Vec<RuntimeEvent> {
+        self.events.take().into_iter().map(RuntimeEvent::Balances).collect()
+    }
+
+    fn journaled(&mut self) -> Vec<&mut dyn Transactional> {
+        vec![&mut self.balance, &mut self.total_issuance, &mut self.events]
+    }
+
+    fn clone_box(&self) -> Box<dyn pallet::Pallet> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
 mod tests {
     use super::*;
 
@@ -79,63 +172,78 @@ mod tests {
     #[test]
     fn test_balance_get() {
         let mut pallet = Pallet::new();
-        pallet.balance.insert("Alice".to_string(), 100);
+        pallet.balance.insert("Alice".to_string(), Amount(100));
         let balance = pallet.balance("Alice");
-        assert_eq!(balance, 100);
+        assert_eq!(balance, Amount(100));
     }
 
     #[test]
     fn test_balance_add() {
         let mut pallet = Pallet::new();
-        pallet.add_balance("Alice", 100);
+        pallet.add_balance("Alice", Amount(100)).unwrap();
         let balance = pallet.balance("Alice");
-        assert_eq!(balance, 100);
+        assert_eq!(balance, Amount(100));
     }
 
     #[test]
     fn test_balance_sub() {
         let mut pallet = Pallet::new();
-        pallet.add_balance("Alice", 100);
-        pallet.sub_balance("Alice", 50);
+        pallet.add_balance("Alice", Amount(100)).unwrap();
+        pallet.sub_balance("Alice", Amount(50)).unwrap();
         let balance = pallet.balance("Alice");
-        assert_eq!(balance, 50);
+        assert_eq!(balance, Amount(50));
     }
 
     #[test]
     fn test_transfer() {
         let mut pallet = Pallet::new();
-        pallet.add_balance("Alice", 100);
-        pallet.transfer("Alice", "Bob", 50).unwrap();
+        pallet.add_balance("Alice", Amount(100)).unwrap();
+        pallet.transfer("Alice", "Bob", Amount(50)).unwrap();
         let alice_balance = pallet.balance("Alice");
         let bob_balance = pallet.balance("Bob");
-        assert_eq!(alice_balance, 50);
-        assert_eq!(bob_balance, 50);
+        assert_eq!(alice_balance, Amount(50));
+        assert_eq!(bob_balance, Amount(50));
     }
 
     #[test]
     fn test_transfer_insufficient_balance() {
         let mut pallet = Pallet::new();
-        pallet.set_balance("Alice", 100);
-        let result = pallet.transfer("Alice", "Bob", 150);
-        assert_eq!(result.is_err(), true);
+        pallet.set_balance("Alice", Amount(100)).unwrap();
+        let result = pallet.transfer("Alice", "Bob", Amount(150));
+        assert!(result.is_err());
         assert_eq!(result.unwrap_err(), "Insufficient balance");
     }
 
     #[test]
     fn test_transfer_zero_amount() {
         let mut pallet = Pallet::new();
-        pallet.set_balance("Alice", 100);
-        let result = pallet.transfer("Alice", "Bob", 0);
-        assert_eq!(result.is_err(), true);
+        pallet.set_balance("Alice", Amount(100)).unwrap();
+        let result = pallet.transfer("Alice", "Bob", Amount(0));
+        assert!(result.is_err());
         assert_eq!(result.unwrap_err(), "Amount must be greater than 0");
     }
 
     #[test]
     fn test_set_balance() {
         let mut pallet = Pallet::new();
-        pallet.set_balance("Alice", 100);
+        pallet.set_balance("Alice", Amount(100)).unwrap();
         let balance = pallet.balance("Alice");
-        assert_eq!(balance, 100);
+        assert_eq!(balance, Amount(100));
+        pallet.set_balance("Alice", Amount(40)).unwrap();
+        assert_eq!(*pallet.total_issuance, Amount(40));
+    }
+
+    #[test]
+    fn test_mint() {
+        let mut pallet = Pallet::new();
+        pallet.set_balance("Alice", Amount(100)).unwrap();
+        pallet.mint("Bob", Amount(50)).unwrap();
+        pallet.transfer("Bob", "Alice", Amount(20)).unwrap();
+        assert_eq!(pallet.balance("Bob"), Amount(30));
+        assert_eq!(*pallet.total_issuance, Amount(150));
+
+        assert!(pallet.mint("Bob", Amount(u64::MAX)).is_err());
+        assert_eq!(*pallet.total_issuance, Amount(150));
     }
 
 
@@ -0,0 +1,143 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use chrono::DateTime;
+use digest::Digest;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use crate::domain::account;
+use crate::domain::amount::Amount;
+use crate::core_client::pow::MAX_DIFFICULTY;
+
+/// Most decimal places a `u64` amount can be scaled by.
+const MAX_DECIMALS: u32 = 19;
+
+/// Everything nodes must agree on before block 1: the genesis block and state, and the
+/// consensus parameters. Two nodes started from the same spec produce the same block 0.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChainSpec {
+    pub chain_id: String,
+    /// Decimal places amounts are shown and entered with, see `Amount::format`.
+    pub decimals: u32,
+    pub genesis_timestamp: String,
+    /// Leading zero bits the first blocks' proof of work must have; retargeted from then on.
+    pub initial_difficulty: u32,
+    /// Difficulty never drops below this.
+    pub min_difficulty: u32,
+    /// Block time the difficulty adjustment aims for.
+    pub target_block_time_secs: u64,
+    /// Number of blocks between difficulty adjustments.
+    pub difficulty_adjustment_interval: usize,
+    /// Most transactions a block may contain.
+    pub max_block_transactions: usize,
+    /// Most bytes of transactions, as counted by `Transaction::encoded_len`, a block may contain.
+    pub max_block_bytes: usize,
+    /// Coinbase reward of the first blocks, halved every `halving_interval` blocks.
+    pub initial_block_reward: Amount,
+    pub halving_interval: usize,
+    pub balances: BTreeMap<String, Amount>,
+}
+
+impl ChainSpec {
+    /// Local development chain: Alice's dev account starts with 100.
+    pub fn development() -> Self {
+        let mut balances = BTreeMap::new();
+        balances.insert(account::dev_address("Alice"), Amount(100));
+        ChainSpec {
+            chain_id: account::DEV_CHAIN_ID.to_string(),
+            decimals: 2,
+            genesis_timestamp: "2024-11-22T00:00:00+00:00".to_string(),
+            initial_difficulty: 16,
+            min_difficulty: 8,
+            target_block_time_secs: 10,
+            difficulty_adjustment_interval: 10,
+            max_block_transactions: 5,
+            max_block_bytes: 100_000,
+            initial_block_reward: Amount(50),
+            halving_interval: 100,
+            balances,
+        }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path).map_err(|e| format!("Could not read chain spec {}: {}", path.display(), e))?;
+        let spec: ChainSpec = serde_json::from_str(&contents).map_err(|e| format!("Invalid chain spec {}: {}", path.display(), e))?;
+        spec.validate()?;
+        Ok(spec)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.chain_id.is_empty() { return Err("Chain spec needs a chain_id".to_string()); }
+        if self.decimals > MAX_DECIMALS { return Err(format!("decimals cannot exceed {}", MAX_DECIMALS)); }
+        DateTime::parse_from_rfc3339(&self.genesis_timestamp).map_err(|_| "Invalid genesis_timestamp".to_string())?;
+        if self.initial_difficulty > MAX_DIFFICULTY { return Err(format!("Difficulty cannot exceed {} bits", MAX_DIFFICULTY)); }
+        if self.min_difficulty > self.initial_difficulty { return Err("min_difficulty cannot exceed initial_difficulty".to_string()); }
+        if self.target_block_time_secs == 0 { return Err("target_block_time_secs must be greater than 0".to_string()); }
+        if self.difficulty_adjustment_interval == 0 { return Err("difficulty_adjustment_interval must be greater than 0".to_string()); }
+        if self.max_block_transactions == 0 { return Err("max_block_transactions must be greater than 0".to_string()); }
+        if self.max_block_bytes == 0 { return Err("max_block_bytes must be greater than 0".to_string()); }
+        if self.halving_interval == 0 { return Err("halving_interval must be greater than 0".to_string()); }
+        Ok(())
+    }
+
+    /// SHA-256 of the whole spec, committed in the genesis block.
+    pub fn hash(&self) -> String {
+        let encoded = serde_json::to_vec(self).expect("chain spec serializes to JSON");
+        hex::encode(Sha256::digest(encoded))
+    }
+
+    /// Most a coinbase may pay at `height`. Reaches zero after 64 halvings.
+    pub fn block_reward(&self, height: usize) -> Amount {
+        let halvings = (height / self.halving_interval) as u32;
+        Amount(self.initial_block_reward.0.checked_shr(halvings).unwrap_or(0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core_client::storage::tests::temp_dir;
+
+    #[test]
+    fn test_load() {
+        let dir = temp_dir("chain-spec");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("spec.json");
+        let mut spec = ChainSpec::development();
+        spec.chain_id = "abdala-test".to_string();
+        fs::write(&path, serde_json::to_string(&spec).unwrap()).unwrap();
+
+        assert_eq!(ChainSpec::load(&path).unwrap(), spec);
+        assert!(ChainSpec::load(dir.join("missing.json")).is_err());
+    }
+
+    #[test]
+    fn test_block_reward_halves() {
+        let spec = ChainSpec::development();
+        assert_eq!(spec.block_reward(1), Amount(50));
+        assert_eq!(spec.block_reward(spec.halving_interval - 1), Amount(50));
+        assert_eq!(spec.block_reward(spec.halving_interval), Amount(25));
+        assert_eq!(spec.block_reward(spec.halving_interval * 3), Amount(6));
+        assert_eq!(spec.block_reward(spec.halving_interval * 64), Amount(0));
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut spec = ChainSpec::development();
+        spec.genesis_timestamp = "yesterday".to_string();
+        assert_eq!(spec.validate().unwrap_err(), "Invalid genesis_timestamp");
+
+        let mut spec = ChainSpec::development();
+        spec.max_block_transactions = 0;
+        assert!(spec.validate().is_err());
+
+        let mut spec = ChainSpec::development();
+        spec.min_difficulty = spec.initial_difficulty + 1;
+        assert_eq!(spec.validate().unwrap_err(), "min_difficulty cannot exceed initial_difficulty");
+
+        let mut spec = ChainSpec::development();
+        spec.decimals = 20;
+        assert_eq!(spec.validate().unwrap_err(), "decimals cannot exceed 19");
+    }
+}
@@ -0,0 +1,574 @@
+use std::any::Any;
+use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+use crate::core_client::balance::Pallet as BalancesPallet;
+use crate::core_client::chain_spec::ChainSpec;
+use crate::core_client::pallet::{BlockContext, Pallet, RuntimeBuilder, RuntimeEvent};
+use crate::core_client::system::{self, Pallet as SystemPallet};
+use crate::domain::block::Block;
+use crate::domain::call::RuntimeCall;
+use crate::domain::encoding::encode_fields;
+use crate::domain::merkle;
+use crate::domain::transaction::Transaction;
+
+/// The pallets this chain runs. Adding a pallet to the chain means registering it here.
+pub fn runtime() -> RuntimeBuilder {
+    let runtime = RuntimeBuilder::new()
+        .pallet(SystemPallet::new())
+        .pallet(BalancesPallet::new());
+    #[cfg(test)]
+    let runtime = match tests::EXTEND_RUNTIME.get() {
+        Some(extend) => extend(runtime),
+        None => runtime,
+    };
+    runtime
+}
+
+/// Block 0 of the chain described by `spec`, committing to its genesis state. Having no parent,
+/// it commits to the whole spec in `previous_hash`, so nodes share block 0 only if they run the
+/// same chain with the same consensus parameters.
+pub fn genesis_block(spec: &ChainSpec) -> Block {
+    let mut block = Block::new(0, spec.genesis_timestamp.clone(), 0, spec.initial_difficulty, spec.hash(), vec![]);
+    let mut state = State::genesis(spec);
+    block.state_root = state.state_root_after(&block).expect("genesis block has no transactions");
+    block
+}
+
+/// Whether an included transaction's call went through.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ReceiptStatus {
+    Success,
+    /// The call was reverted; the nonce was still used and the fee still paid.
+    Failed { reason: String },
+}
+
+/// Outcome of a transaction included in a block, with the events it deposited.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Receipt {
+    pub transaction_hash: String,
+    pub block_index: usize,
+    pub block_hash: String,
+    pub status: ReceiptStatus,
+    pub events: Vec<RuntimeEvent>,
+}
+
+/// Everything blocks act on: the storage of every pallet in the runtime. Node state is always
+/// `State::genesis(spec)` followed by `execute_block` for every block in the chain, so two nodes
+/// with the same blocks agree on it.
+#[derive(Debug, Clone)]
+pub struct State {
+    pallets: Vec<Box<dyn Pallet>>,
+}
+
+impl State {
+    pub fn genesis(spec: &ChainSpec) -> Self {
+        runtime().build(spec).unwrap()
+    }
+
+    pub fn from_pallets(pallets: Vec<Box<dyn Pallet>>) -> Self {
+        State { pallets }
+    }
+
+    pub fn pallet<P: Pallet>(&self) -> Option<&P> {
+        self.pallets.iter().find_map(|pallet| (pallet.as_ref() as &dyn Any).downcast_ref::<P>())
+    }
+
+    pub fn pallet_mut<P: Pallet>(&mut self) -> Option<&mut P> {
+        self.pallets.iter_mut().find_map(|pallet| (pallet.as_mut() as &mut dyn Any).downcast_mut::<P>())
+    }
+
+    /// The executive needs balances and nonces to charge fees and order transactions, so every
+    /// runtime must include the balances and system pallets.
+    pub fn balances(&self) -> &BalancesPallet {
+        self.pallet().expect("runtime has no balances pallet")
+    }
+
+    pub fn balances_mut(&mut self) -> &mut BalancesPallet {
+        self.pallet_mut().expect("runtime has no balances pallet")
+    }
+
+    pub fn system(&self) -> &SystemPallet {
+        self.pallet().expect("runtime has no system pallet")
+    }
+
+    pub fn system_mut(&mut self) -> &mut SystemPallet {
+        self.pallet_mut().expect("runtime has no system pallet")
+    }
+
+    /// Removes and returns the events every pallet deposited since the last call.
+    pub fn take_events(&mut self) -> Vec<RuntimeEvent> {
+        self.pallets.iter_mut().flat_map(|pallet| pallet.take_events()).collect()
+    }
+
+    /// Storage of every pallet by pallet name, as `Pallet::storage` lists it.
+    pub fn storage(&self) -> BTreeMap<String, Vec<(String, String)>> {
+        self.pallets.iter().map(|pallet| (pallet.name().to_string(), pallet.storage())).collect()
+    }
+
+    /// State of the runtime of `spec` holding `storage`, as `storage` lists it, e.g. from a
+    /// snapshot. Whether it is the state of any block is up to the caller to check.
+    pub fn restore(spec: &ChainSpec, storage: &BTreeMap<String, Vec<(String, String)>>) -> Result<Self, String> {
+        let mut state = State::genesis(spec);
+        if let Some(name) = storage.keys().find(|name| !state.pallets.iter().any(|pallet| pallet.name() == name.as_str())) {
+            return Err(format!("Pallet {} is not part of the runtime", name));
+        }
+        for pallet in &mut state.pallets {
+            pallet.restore(storage.get(pallet.name()).map(Vec::as_slice).unwrap_or_default())?;
+        }
+        Ok(state)
+    }
+
+    /// Merkle root over the storage of every pallet, in registration order. Each leaf is the
+    /// pallet name, key and value, length-prefixed, so two states share a root only if they hold
+    /// the same storage.
+    pub fn state_root(&self) -> String {
+        let leaves: Vec<String> = self.pallets.iter()
+            .flat_map(|pallet| pallet.storage().into_iter().map(move |(key, value)| (pallet.name(), key, value)))
+            .map(|(name, key, value)| hex::encode(encode_fields(&[name.as_bytes(), key.as_bytes(), value.as_bytes()])))
+            .collect();
+        merkle::merkle_root(&leaves)
+    }
+
+    /// Root of the state `block` leads to from this one, which the block builder commits to in
+    /// the header. Leaves this state as it was.
+    pub fn state_root_after(&mut self, block: &Block) -> Result<String, String> {
+        self.dry_run(|state| {
+            state.apply_block(block)?;
+            Ok(state.state_root())
+        })
+    }
+
+    /// Routes `call` to the registered pallet it names.
+    pub fn dispatch(&mut self, origin: &str, call: &RuntimeCall) -> Result<(), String> {
+        let pallet = self.pallets.iter_mut()
+            .find(|pallet| pallet.name() == call.pallet())
+            .ok_or(format!("Pallet {} is not part of the runtime", call.pallet()))?;
+        pallet.dispatch(origin, call)
+    }
+
+    /// State reached by executing `chain` from the genesis state of `spec`, and the receipts of
+    /// every transaction in it.
+    pub fn from_chain(spec: &ChainSpec, chain: &[Block]) -> Result<(Self, Vec<Receipt>), String> {
+        let mut state = State::genesis(spec);
+        let mut receipts = vec![];
+        for block in chain {
+            receipts.extend(state.execute_block(block)?);
+        }
+        Ok((state, receipts))
+    }
+
+    /// Runs `f` in a storage transaction on every pallet and commits its writes only if it
+    /// succeeds, so an error half way through leaves the state exactly as it was. Transactions
+    /// nest; rolling one back costs as much as the writes it undoes.
+    pub fn transactional<T>(&mut self, f: impl FnOnce(&mut State) -> Result<T, String>) -> Result<T, String> {
+        self.pallets.iter_mut().for_each(|pallet| pallet.start_transaction());
+        let result = f(self);
+        match result {
+            Ok(_) => self.pallets.iter_mut().for_each(|pallet| pallet.commit_transaction()),
+            Err(_) => self.pallets.iter_mut().for_each(|pallet| pallet.rollback_transaction()),
+        }
+        result
+    }
+
+    /// Runs `f` in a storage transaction that is always rolled back, e.g. to see where a block
+    /// would lead without applying it.
+    pub fn dry_run<T>(&mut self, f: impl FnOnce(&mut State) -> T) -> T {
+        self.pallets.iter_mut().for_each(|pallet| pallet.start_transaction());
+        let result = f(self);
+        self.pallets.iter_mut().for_each(|pallet| pallet.rollback_transaction());
+        result
+    }
+
+    /// Applies a signed transaction. An invalid one (bad signature, chain id or nonce, or a sender
+    /// who cannot pay for it) is an error and changes nothing. A valid one uses its nonce and pays its fee to
+    /// `author`, or burns it when the block has no author to pay; its call is then dispatched to
+    /// the pallet it names and is reverted as a whole if it fails, which the returned status
+    /// records.
+    pub fn execute_transaction(&mut self, transaction: &Transaction, author: Option<&str>) -> Result<ReceiptStatus, String> {
+        transaction.verify()?;
+        if transaction.chain_id != self.system().chain_id { return Err("Wrong chain id".to_string()); }
+
+        let sender = &transaction.sender;
+        let fee = transaction.fee;
+
+        let expected_nonce = self.system().get_nonce(sender);
+        if transaction.nonce < expected_nonce { return Err("Stale nonce".to_string()); }
+        if transaction.nonce > expected_nonce { return Err("Future nonce".to_string()); }
+
+        let total = transaction.call.value().checked_add(fee).ok_or("Amount overflow")?;
+        if self.balances().balance(sender) < total {
+            return Err("Insufficient balance".to_string());
+        }
+
+        self.transactional(|state| {
+            state.system_mut().increment_nonce(sender)?;
+            if !fee.is_zero() {
+                match author {
+                    Some(author) => state.balances_mut().transfer(sender, author, fee)?,
+                    None => state.balances_mut().burn(sender, fee)?,
+                }
+            }
+            match state.transactional(|state| state.dispatch(sender, &transaction.call)) {
+                Ok(()) => Ok(ReceiptStatus::Success),
+                Err(reason) => {
+                    state.system_mut().deposit_event(system::Event::ExtrinsicFailed { reason: reason.clone() });
+                    Ok(ReceiptStatus::Failed { reason })
+                }
+            }
+        })
+    }
+
+    /// Mints the block reward. Validation has already capped the amount and checked it goes to a
+    /// beneficiary.
+    pub fn execute_coinbase(&mut self, coinbase: &Transaction) -> Result<(), String> {
+        match coinbase.coinbase_payout() {
+            Some((beneficiary, amount)) if !amount.is_zero() => self.balances_mut().mint(beneficiary, amount),
+            _ => Ok(()),
+        }
+    }
+
+    /// Runs every pallet's `on_initialize`, the coinbase and then the block's transactions in
+    /// order, paying fees to the coinbase beneficiary, and finally every pallet's `on_finalize`.
+    /// Returns a receipt per transaction. Blocks only carry valid transactions and commit to the
+    /// state they lead to, so an invalid transaction or a state root that does not match ours
+    /// makes the whole block invalid and the state is left as it was before the block.
+    pub fn execute_block(&mut self, block: &Block) -> Result<Vec<Receipt>, String> {
+        self.transactional(|state| {
+            let receipts = state.apply_block(block)?;
+            if state.state_root() != block.state_root {
+                return Err(format!("Block {} has an invalid state root", block.index));
+            }
+            Ok(receipts)
+        })
+    }
+
+    /// `execute_block` without the state root check. Leaves the state half way through the
+    /// block on error.
+    fn apply_block(&mut self, block: &Block) -> Result<Vec<Receipt>, String> {
+        let context = BlockContext::of(block);
+        let block_hash = block.hash();
+        self.initialize_block(&context);
+        // Receipts only cover transactions; hook events are not kept.
+        self.take_events();
+        let mut receipts = vec![];
+        for (position, transaction) in block.transactions.iter().enumerate() {
+            let result = if position == 0 && transaction.is_coinbase() {
+                self.execute_coinbase(transaction).map(|()| ReceiptStatus::Success)
+            } else {
+                self.execute_transaction(transaction, context.author.as_deref())
+            };
+            let status = result.map_err(|e| format!("Block {} has an invalid transaction {}: {}", block.index, transaction.hash, e))?;
+            receipts.push(Receipt {
+                transaction_hash: transaction.hash.clone(),
+                block_index: block.index,
+                block_hash: block_hash.clone(),
+                status,
+                events: self.take_events(),
+            });
+        }
+        self.finalize_block(&context);
+        self.take_events();
+        Ok(receipts)
+    }
+
+    /// Runs every pallet's `on_initialize`, in registration order.
+    pub fn initialize_block(&mut self, block: &BlockContext) {
+        for pallet in &mut self.pallets {
+            pallet.on_initialize(block);
+        }
+    }
+
+    /// Runs every pallet's `on_finalize`, in registration order.
+    pub fn finalize_block(&mut self, block: &BlockContext) {
+        for pallet in &mut self.pallets {
+            pallet.on_finalize(block);
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use crate::core_client::balance;
+    use crate::domain::account;
+    use crate::domain::transaction::tests::dev_transfer;
+    use crate::domain::amount::Amount;
+
+    type ExtendRuntime = fn(RuntimeBuilder) -> RuntimeBuilder;
+
+    thread_local! {
+        /// Registers extra pallets in `runtime` for the current test thread, so a whole node can
+        /// run on a test pallet.
+        pub(crate) static EXTEND_RUNTIME: Cell<Option<ExtendRuntime>> = const { Cell::new(None) };
+    }
+
+    /// Block 1 committing to the state it leads to from `state`.
+    fn block(state: &mut State, transactions: Vec<Transaction>) -> Block {
+        let mut block = Block::new(1, "2021-08-01T00:00:00+00:00".to_string(), 0, 16, "0".to_string(), transactions);
+        block.state_root = state.state_root_after(&block).unwrap_or_default();
+        block
+    }
+
+    #[test]
+    fn test_genesis_block_is_deterministic() {
+        let spec = ChainSpec::development();
+        assert_eq!(genesis_block(&spec).hash(), genesis_block(&ChainSpec::development()).hash());
+        assert_eq!(genesis_block(&spec).timestamp, spec.genesis_timestamp);
+
+        let mut other = ChainSpec::development();
+        other.chain_id = "abdala-test".to_string();
+        assert_ne!(genesis_block(&other).hash(), genesis_block(&spec).hash());
+        let mut other = ChainSpec::development();
+        other.halving_interval += 1;
+        assert_ne!(genesis_block(&other).hash(), genesis_block(&spec).hash());
+    }
+
+    #[test]
+    fn test_transaction_for_another_chain_is_invalid() {
+        let mut spec = ChainSpec::development();
+        spec.chain_id = "abdala-test".to_string();
+        let mut state = State::genesis(&spec);
+        let alice = account::dev_address("Alice");
+        assert_eq!(state.execute_transaction(&dev_transfer("Alice", 0, "Bob", 10, 0), None).unwrap_err(), "Wrong chain id");
+        assert_eq!(state.system().get_nonce(&alice), 0);
+
+        let transaction = Transaction::transfer(&account::dev_key("Alice"), &spec.chain_id, 0, account::dev_address("Bob"), Amount(10), Amount::ZERO);
+        state.execute_transaction(&transaction, None).unwrap();
+        assert_eq!(state.balances().balance(&alice), Amount(90));
+    }
+
+    #[test]
+    fn test_genesis() {
+        let state = State::genesis(&ChainSpec::development());
+        assert_eq!(state.balances().balance(&account::dev_address("Alice")), Amount(100));
+        assert!(state.system().nonce.is_empty());
+    }
+
+    #[test]
+    fn test_execute_block_is_atomic() {
+        let mut state = State::genesis(&ChainSpec::development());
+        let alice = account::dev_address("Alice");
+        let failing = dev_transfer("Bob", 0, "Alice", 500, 0);
+        let next = block(&mut state, vec![dev_transfer("Alice", 0, "Bob", 40, 0), failing.clone()]);
+        let result = state.execute_block(&next);
+        assert_eq!(result.unwrap_err(), format!("Block 1 has an invalid transaction {}: Insufficient balance", failing.hash));
+        assert_eq!(state.balances().balance(&alice), Amount(100));
+        assert_eq!(state.system().get_nonce(&alice), 0);
+
+        let next = block(&mut state, vec![dev_transfer("Alice", 0, "Bob", 40, 0)]);
+        state.execute_block(&next).unwrap();
+        assert_eq!(state.balances().balance(&alice), Amount(60));
+        assert_eq!(state.balances().balance(&account::dev_address("Bob")), Amount(40));
+        assert_eq!(state.system().get_nonce(&alice), 1);
+    }
+
+    #[test]
+    fn test_invalid_transaction_keeps_nonce() {
+        let mut state = State::genesis(&ChainSpec::development());
+        let alice = account::dev_address("Alice");
+        assert!(state.execute_transaction(&dev_transfer("Alice", 0, "Bob", 500, 0), None).is_err());
+        assert_eq!(state.system().get_nonce(&alice), 0);
+        assert!(state.take_events().is_empty());
+        assert_eq!(state.execute_transaction(&dev_transfer("Alice", 0, "Bob", 10, 0), None).unwrap(), ReceiptStatus::Success);
+        assert_eq!(state.system().get_nonce(&alice), 1);
+    }
+
+    #[test]
+    fn test_failed_call_uses_nonce_and_pays_fee() {
+        let mut state = State::genesis(&ChainSpec::development());
+        let alice = account::dev_address("Alice");
+        let empty = dev_transfer("Alice", 0, "Bob", 0, 2);
+        let status = state.execute_transaction(&empty, None).unwrap();
+        assert_eq!(status, ReceiptStatus::Failed { reason: "Amount must be greater than 0".to_string() });
+        assert_eq!(state.system().get_nonce(&alice), 1);
+        assert_eq!(state.balances().balance(&alice), Amount(98));
+        assert_eq!(state.take_events(), vec![
+            RuntimeEvent::System(system::Event::NonceIncremented { account: alice.clone(), nonce: 1 }),
+            RuntimeEvent::System(system::Event::ExtrinsicFailed { reason: "Amount must be greater than 0".to_string() }),
+            RuntimeEvent::Balances(balance::Event::Burned { from: alice, amount: Amount(2) }),
+        ]);
+    }
+
+    #[test]
+    fn test_execute_block_returns_receipts() {
+        let mut state = State::genesis(&ChainSpec::development());
+        let alice = account::dev_address("Alice");
+        let bob = account::dev_address("Bob");
+        let miner = account::dev_address("Miner");
+        let coinbase = Transaction::coinbase(1, "2021-08-01T00:00:00+00:00".to_string(), miner.clone(), Amount(50));
+        let payment = dev_transfer("Alice", 0, "Bob", 10, 0);
+        let block = block(&mut state, vec![coinbase.clone(), payment.clone()]);
+        let receipts = state.execute_block(&block).unwrap();
+
+        assert_eq!(receipts.len(), 2);
+        assert_eq!(receipts[0].transaction_hash, coinbase.hash);
+        assert_eq!(receipts[0].events, vec![RuntimeEvent::Balances(balance::Event::Minted { to: miner, amount: Amount(50) })]);
+        assert_eq!(receipts[1], Receipt {
+            transaction_hash: payment.hash,
+            block_index: 1,
+            block_hash: block.hash(),
+            status: ReceiptStatus::Success,
+            events: vec![
+                RuntimeEvent::System(system::Event::NonceIncremented { account: alice.clone(), nonce: 1 }),
+                RuntimeEvent::Balances(balance::Event::Transfer { from: alice, to: bob, amount: Amount(10) }),
+            ],
+        });
+    }
+
+    #[test]
+    fn test_execute_remark() {
+        let mut state = State::genesis(&ChainSpec::development());
+        let alice = account::dev_address("Alice");
+        let remark = RuntimeCall::System(system::Call::Remark { message: "gm".to_string() });
+        state.execute_transaction(&Transaction::new(&account::dev_key("Alice"), account::DEV_CHAIN_ID, 0, remark, Amount(2)), None).unwrap();
+        assert_eq!(state.balances().balance(&alice), Amount(98));
+        assert_eq!(state.system().get_nonce(&alice), 1);
+    }
+
+    #[test]
+    fn test_transactional_reverts_on_error() {
+        let mut state = State::genesis(&ChainSpec::development());
+        let alice = account::dev_address("Alice");
+        let result: Result<(), String> = state.transactional(|state| {
+            state.system_mut().increment_nonce(&alice)?;
+            state.balances_mut().burn(&alice, Amount(10))?;
+            Err("Aborted".to_string())
+        });
+        assert_eq!(result.unwrap_err(), "Aborted");
+        assert_eq!(state.system().get_nonce(&alice), 0);
+        assert_eq!(*state.balances().total_issuance, Amount(100));
+        assert!(state.take_events().is_empty());
+
+        // An inner rollback keeps what the outer transaction wrote before it.
+        state.transactional(|state| {
+            state.system_mut().increment_nonce(&alice)?;
+            let inner: Result<(), String> = state.transactional(|state| {
+                state.balances_mut().burn(&alice, Amount(10))?;
+                Err("Aborted".to_string())
+            });
+            assert!(inner.is_err());
+            Ok(())
+        }).unwrap();
+        assert_eq!(state.system().get_nonce(&alice), 1);
+        assert_eq!(state.balances().balance(&alice), Amount(100));
+        assert_eq!(state.take_events(), vec![RuntimeEvent::System(system::Event::NonceIncremented { account: alice.clone(), nonce: 1 })]);
+    }
+
+    #[test]
+    fn test_state_root_after_leaves_state_as_it_was() {
+        let mut state = State::genesis(&ChainSpec::development());
+        let root = state.state_root();
+        let next = block(&mut state, vec![dev_transfer("Alice", 0, "Bob", 10, 0)]);
+        assert_ne!(next.state_root, root);
+        assert_eq!(state.state_root(), root);
+        assert!(state.take_events().is_empty());
+    }
+
+    #[test]
+    fn test_execute_block_mints_coinbase() {
+        let mut state = State::genesis(&ChainSpec::development());
+        let miner = account::dev_address("Miner");
+        let coinbase = Transaction::coinbase(1, "2021-08-01T00:00:00+00:00".to_string(), miner.clone(), Amount(50));
+        let next = block(&mut state, vec![coinbase.clone(), dev_transfer("Alice", 0, "Bob", 10, 0)]);
+        state.execute_block(&next).unwrap();
+        assert_eq!(state.balances().balance(&miner), Amount(50));
+        assert_eq!(*state.balances().total_issuance, Amount(150));
+
+        // Only the first transaction may mint.
+        let next = block(&mut state, vec![dev_transfer("Alice", 1, "Bob", 10, 0), coinbase]);
+        assert!(state.execute_block(&next).is_err());
+        assert_eq!(state.balances().balance(&miner), Amount(50));
+    }
+
+    #[test]
+    fn test_execute_block_pays_fees_to_author() {
+        let mut state = State::genesis(&ChainSpec::development());
+        let alice = account::dev_address("Alice");
+        let miner = account::dev_address("Miner");
+        let paying = |nonce, fee| dev_transfer("Alice", nonce, "Bob", 10, fee);
+
+        let coinbase = Transaction::coinbase(1, "2021-08-01T00:00:00+00:00".to_string(), miner.clone(), Amount::ZERO);
+        let next = block(&mut state, vec![coinbase, paying(0, 3)]);
+        state.execute_block(&next).unwrap();
+        assert_eq!(state.balances().balance(&alice), Amount(87));
+        assert_eq!(state.balances().balance(&miner), Amount(3));
+
+        // Without an author the fee is burned.
+        let next = block(&mut state, vec![paying(1, 2)]);
+        state.execute_block(&next).unwrap();
+        assert_eq!(state.balances().balance(&alice), Amount(75));
+        assert_eq!(*state.balances().total_issuance, Amount(98));
+
+        // The fee counts towards the balance check.
+        assert_eq!(state.execute_transaction(&paying(2, 66), None).unwrap_err(), "Insufficient balance");
+    }
+
+    #[test]
+    fn test_from_chain_is_deterministic() {
+        let spec = ChainSpec::development();
+        let mut state = State::genesis(&spec);
+        let mut chain = vec![genesis_block(&spec)];
+        for transactions in [
+            vec![dev_transfer("Alice", 0, "Bob", 30, 0)],
+            vec![dev_transfer("Bob", 0, "Carol", 5, 0), dev_transfer("Alice", 1, "Carol", 1, 0)],
+        ] {
+            let block = block(&mut state, transactions);
+            state.execute_block(&block).unwrap();
+            chain.push(block);
+        }
+        let first = State::from_chain(&spec, &chain).unwrap().0;
+        let second = State::from_chain(&spec, &chain).unwrap().0;
+        assert_eq!(*first.balances().balance, *second.balances().balance);
+        assert_eq!(*first.system().nonce, *second.system().nonce);
+        assert_eq!(first.balances().balance(&account::dev_address("Carol")), Amount(6));
+        assert_eq!(first.state_root(), state.state_root());
+    }
+
+    #[test]
+    fn test_execute_block_checks_state_root() {
+        let mut state = State::genesis(&ChainSpec::development());
+        let genesis_root = state.state_root();
+        let mut forged = block(&mut state, vec![dev_transfer("Alice", 0, "Bob", 10, 0)]);
+        forged.state_root = genesis_root.clone();
+        assert_eq!(state.execute_block(&forged).unwrap_err(), "Block 1 has an invalid state root");
+        assert_eq!(state.state_root(), genesis_root);
+
+        let valid = block(&mut state, vec![dev_transfer("Alice", 0, "Bob", 10, 0)]);
+        assert_ne!(valid.state_root, genesis_root);
+        state.execute_block(&valid).unwrap();
+        assert_eq!(state.state_root(), valid.state_root);
+    }
+
+    #[test]
+    fn test_restore_round_trips_storage() {
+        let spec = ChainSpec::development();
+        let mut state = State::genesis(&spec);
+        let next = block(&mut state, vec![dev_transfer("Alice", 0, "Bob", 10, 0)]);
+        state.execute_block(&next).unwrap();
+
+        let restored = State::restore(&spec, &state.storage()).unwrap();
+        assert_eq!(restored.state_root(), state.state_root());
+        assert_eq!(restored.system().get_nonce(&account::dev_address("Alice")), 1);
+
+        let mut unknown = state.storage();
+        unknown.insert("Staking".to_string(), vec![]);
+        assert_eq!(State::restore(&spec, &unknown).unwrap_err(), "Pallet Staking is not part of the runtime");
+        let mut invalid = state.storage();
+        invalid.get_mut("System").unwrap().push(("nonce/Bob".to_string(), "-1".to_string()));
+        assert_eq!(State::restore(&spec, &invalid).unwrap_err(), "Invalid System storage value -1");
+    }
+
+    #[test]
+    fn test_state_root_covers_every_pallet() {
+        let state = State::genesis(&ChainSpec::development());
+        let root = state.state_root();
+        let mut bumped = state.clone();
+        bumped.system_mut().increment_nonce(&account::dev_address("Alice")).unwrap();
+        assert_ne!(bumped.state_root(), root);
+        let mut minted = state.clone();
+        minted.balances_mut().mint(&account::dev_address("Alice"), Amount(1)).unwrap();
+        assert_ne!(minted.state_root(), root);
+        assert_eq!(state.clone().state_root(), root);
+    }
+}
@@ -0,0 +1,97 @@
+use std::cmp::Ordering;
+use crate::domain::block::Block;
+
+/// Expected number of hashes to meet `difficulty` leading zero bits. Saturates above 127 bits,
+/// far beyond anything a chain here will reach.
+pub fn block_work(difficulty: u32) -> u128 {
+    1u128.checked_shl(difficulty).unwrap_or(u128::MAX)
+}
+
+/// Total work behind `chain`, genesis excluded since every chain on the spec shares it.
+pub fn chain_work(chain: &[Block]) -> u128 {
+    chain.iter().skip(1).fold(0, |total, block| total.saturating_add(block_work(block.difficulty)))
+}
+
+/// Orders two chains by fork choice: the one with more cumulative work is greater. Equal work is
+/// broken by the lower tip hash, so every node picks the same chain whatever order it saw them in.
+pub fn compare_chains(a: &[Block], b: &[Block]) -> Ordering {
+    chain_work(a).cmp(&chain_work(b)).then_with(|| {
+        let a_tip = a.last().map(|block| block.hash());
+        let b_tip = b.last().map(|block| block.hash());
+        b_tip.cmp(&a_tip)
+    })
+}
+
+/// Whether `candidate` should replace `current`. Assumes both chains are valid: the declared
+/// difficulties are only meaningful once `validate_chain` has checked them.
+pub fn is_better_chain(current: &[Block], candidate: &[Block]) -> bool {
+    compare_chains(candidate, current) == Ordering::Greater
+}
+
+/// Summary of switching from one branch to another.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reorg {
+    /// Height of the last block both branches share.
+    pub common_ancestor: usize,
+    /// Our blocks above the common ancestor that were rolled back.
+    pub depth: usize,
+    /// Blocks of the new branch applied on top of the common ancestor.
+    pub applied: usize,
+    /// Hashes of orphaned transactions returned to the pool.
+    pub reinjected: Vec<String>,
+}
+
+/// Height of the last block `a` and `b` have in common, or `None` if not even genesis matches.
+pub fn common_ancestor(a: &[Block], b: &[Block]) -> Option<usize> {
+    a.iter().zip(b).take_while(|(a, b)| a.hash() == b.hash()).count().checked_sub(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core_client::executive::genesis_block;
+    use crate::core_client::chain_spec::ChainSpec;
+
+    fn chain(difficulties: &[u32], timestamp: &str) -> Vec<Block> {
+        let mut chain = vec![genesis_block(&ChainSpec::development())];
+        for (i, difficulty) in difficulties.iter().enumerate() {
+            let previous_hash = chain.last().unwrap().hash();
+            chain.push(Block::new(i + 1, timestamp.to_string(), 0, *difficulty, previous_hash, vec![]));
+        }
+        chain
+    }
+
+    #[test]
+    fn test_chain_work() {
+        assert_eq!(block_work(16), 65536);
+        assert_eq!(block_work(300), u128::MAX);
+        assert_eq!(chain_work(&chain(&[], "2025-01-01T00:00:00+00:00")), 0);
+        assert_eq!(chain_work(&chain(&[16, 17], "2025-01-01T00:00:00+00:00")), 65536 + 131072);
+    }
+
+    #[test]
+    fn test_more_work_beats_more_blocks() {
+        let long_and_easy = chain(&[8, 8, 8, 8], "2025-01-01T00:00:00+00:00");
+        let short_and_hard = chain(&[16], "2025-01-01T00:00:00+00:00");
+        assert!(is_better_chain(&long_and_easy, &short_and_hard));
+        assert!(!is_better_chain(&short_and_hard, &long_and_easy));
+    }
+
+    #[test]
+    fn test_common_ancestor() {
+        let a = chain(&[16, 16], "2025-01-01T00:00:00+00:00");
+        let mut b = a[..2].to_vec();
+        b.push(Block::new(2, "2025-01-01T00:00:09+00:00".to_string(), 0, 16, b[1].hash(), vec![]));
+        assert_eq!(common_ancestor(&a, &b), Some(1));
+        assert_eq!(common_ancestor(&a, &a[..2]), Some(1));
+        assert_eq!(common_ancestor(&a, &[]), None);
+    }
+
+    #[test]
+    fn test_ties_are_broken_deterministically() {
+        let a = chain(&[16], "2025-01-01T00:00:00+00:00");
+        let b = chain(&[16], "2025-01-01T00:00:01+00:00");
+        assert_ne!(is_better_chain(&a, &b), is_better_chain(&b, &a));
+        assert!(!is_better_chain(&a, &a));
+    }
+}
@@ -0,0 +1,297 @@
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use chrono::{DateTime, Duration, Utc};
+use crate::core_client::authoring::compare_priority;
+use crate::core_client::executive::State;
+use crate::domain::amount::Amount;
+use crate::domain::transaction::Transaction;
+
+/// Node-local admission rules for the pool.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MempoolConfig {
+    /// Once full, a new transaction must outbid the worst pooled one to get in.
+    pub max_transactions: usize,
+    /// Transactions not included in a block within this long are dropped.
+    pub max_age: Duration,
+    /// Fee per 1000 bytes a transaction must pay to be accepted, and by how much a replacement
+    /// must outbid the transaction it replaces.
+    pub min_relay_fee_per_kb: Amount,
+}
+
+impl Default for MempoolConfig {
+    fn default() -> Self {
+        MempoolConfig {
+            max_transactions: 1000,
+            max_age: Duration::hours(1),
+            min_relay_fee_per_kb: Amount::ZERO,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    transaction: Transaction,
+    added: DateTime<Utc>,
+}
+
+/// Transactions waiting for a block, keyed by hash. Everything in the pool was valid against the
+/// state when it was admitted: signed by its sender, not stale, and affordable together with the
+/// sender's other pending transactions.
+#[derive(Debug, Clone)]
+pub struct Mempool {
+    config: MempoolConfig,
+    entries: BTreeMap<String, Entry>,
+}
+
+impl Mempool {
+    pub fn new(config: MempoolConfig) -> Self {
+        Mempool { config, entries: BTreeMap::new() }
+    }
+
+    pub fn set_config(&mut self, config: MempoolConfig) {
+        self.config = config;
+    }
+
+    pub fn contains(&self, hash: &str) -> bool {
+        self.entries.contains_key(hash)
+    }
+
+    pub fn transactions(&self) -> impl Iterator<Item = &Transaction> {
+        self.entries.values().map(|entry| &entry.transaction)
+    }
+
+    /// Admits `transaction` if it is valid against `state`. A transaction with the same sender and
+    /// nonce as a pooled one replaces it when it pays at least the minimum relay fee more.
+    pub fn add(&mut self, transaction: Transaction, state: &State, now: DateTime<Utc>) -> Result<(), String> {
+        transaction.verify()?;
+        if transaction.chain_id != state.system().chain_id {
+            return Err("Wrong chain id".to_string());
+        }
+        if self.contains(&transaction.hash) {
+            return Err("Transaction already in pool".to_string());
+        }
+        if transaction.nonce < state.system().get_nonce(&transaction.sender) {
+            return Err("Stale nonce".to_string());
+        }
+        let min_fee = min_relay_fee(self.config.min_relay_fee_per_kb, transaction.encoded_len());
+        if transaction.fee < min_fee {
+            return Err("Fee below minimum relay fee".to_string());
+        }
+
+        let replaced = self.transactions()
+            .find(|pooled| pooled.sender == transaction.sender && pooled.nonce == transaction.nonce)
+            .map(|pooled| (pooled.hash.clone(), pooled.fee));
+        if let Some((_, fee)) = &replaced {
+            let outbids = transaction.fee.checked_sub(*fee).is_some_and(|bump| !bump.is_zero() && bump >= min_fee);
+            if !outbids {
+                return Err("Replacement fee too low".to_string());
+            }
+        }
+
+        let mut spending = cost(&transaction)?;
+        for pooled in self.transactions() {
+            if pooled.sender == transaction.sender && Some(&pooled.hash) != replaced.as_ref().map(|(hash, _)| hash) {
+                spending = spending.checked_add(cost(pooled)?).ok_or("Amount overflow")?;
+            }
+        }
+        if state.balances().balance(&transaction.sender) < spending {
+            return Err("Insufficient balance".to_string());
+        }
+
+        match replaced {
+            Some((hash, _)) => {
+                self.entries.remove(&hash);
+            }
+            None if self.entries.len() >= self.config.max_transactions => {
+                let worst = self.eviction_candidate().ok_or("Mempool full")?;
+                if compare_priority(&transaction, &self.entries[&worst].transaction) != Ordering::Less {
+                    return Err("Mempool full".to_string());
+                }
+                self.entries.remove(&worst);
+            }
+            None => {}
+        }
+        self.entries.insert(transaction.hash.clone(), Entry { transaction, added: now });
+        Ok(())
+    }
+
+    /// Lowest priority transaction among each sender's last nonce, so that evicting it never
+    /// leaves a later nonce of the same sender stranded.
+    fn eviction_candidate(&self) -> Option<String> {
+        let mut last: BTreeMap<&String, &Transaction> = BTreeMap::new();
+        for transaction in self.transactions() {
+            let entry = last.entry(&transaction.sender).or_insert(transaction);
+            if transaction.nonce > entry.nonce {
+                *entry = transaction;
+            }
+        }
+        last.into_values().max_by(|a, b| compare_priority(a, b)).map(|transaction| transaction.hash.clone())
+    }
+
+    /// Transactions whose nonces follow on from the sender's current nonce without gaps, in nonce
+    /// order per sender. Future-nonce transactions stay in the pool until the missing nonces arrive.
+    pub fn ready(&self, state: &State) -> Vec<Transaction> {
+        let mut pending: Vec<&Transaction> = self.transactions().collect();
+        pending.sort_by_key(|transaction| transaction.nonce);
+
+        let mut next_nonce: BTreeMap<String, u64> = BTreeMap::new();
+        let mut ready = vec![];
+        for transaction in pending {
+            let expected = next_nonce.entry(transaction.sender.clone())
+                .or_insert_with(|| state.system().get_nonce(&transaction.sender));
+            if transaction.nonce == *expected {
+                ready.push(transaction.clone());
+                *expected += 1;
+            }
+        }
+        ready
+    }
+
+    /// Drops transactions made stale by `state`, the ones that waited longer than `max_age`, and
+    /// the ones their sender can no longer afford after paying for its earlier nonces.
+    pub fn prune(&mut self, state: &State, now: DateTime<Utc>) {
+        let max_age = self.config.max_age;
+        self.entries.retain(|_, entry| {
+            entry.transaction.nonce >= state.system().get_nonce(&entry.transaction.sender) && now - entry.added <= max_age
+        });
+
+        let mut pending: Vec<&Transaction> = self.transactions().collect();
+        pending.sort_by_key(|transaction| transaction.nonce);
+        let mut spending: BTreeMap<&String, Amount> = BTreeMap::new();
+        let mut unaffordable = vec![];
+        for transaction in pending {
+            let spent = spending.entry(&transaction.sender).or_insert(Amount::ZERO);
+            match cost(transaction).ok().and_then(|cost| spent.checked_add(cost)) {
+                Some(total) if total <= state.balances().balance(&transaction.sender) => *spent = total,
+                _ => unaffordable.push(transaction.hash.clone()),
+            }
+        }
+        for hash in unaffordable {
+            self.entries.remove(&hash);
+        }
+    }
+}
+
+/// Smallest fee a transaction of `size` bytes must pay to be accepted into our pool.
+pub fn min_relay_fee(fee_per_kb: Amount, size: usize) -> Amount {
+    let fee = (fee_per_kb.0 as u128 * size as u128).div_ceil(1000);
+    Amount(fee.min(u64::MAX as u128) as u64)
+}
+
+fn cost(transaction: &Transaction) -> Result<Amount, String> {
+    transaction.call.value().checked_add(transaction.fee).ok_or("Amount overflow".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core_client::chain_spec::ChainSpec;
+    use crate::domain::account;
+    use crate::domain::transaction::tests::dev_transfer;
+
+    fn genesis() -> State {
+        State::genesis(&ChainSpec::development())
+    }
+
+    #[test]
+    fn test_add_validates_and_deduplicates() {
+        let state = genesis();
+        let mut mempool = Mempool::new(MempoolConfig::default());
+        let transaction = dev_transfer("Alice", 0, "Bob", 10, 0);
+        mempool.add(transaction.clone(), &state, Utc::now()).unwrap();
+        assert_eq!(mempool.add(transaction.clone(), &state, Utc::now()).unwrap_err(), "Transaction already in pool");
+
+        let mut forged = dev_transfer("Mallory", 0, "Bob", 10, 0);
+        forged.sender = account::dev_address("Alice");
+        forged.hash = forged.compute_hash();
+        assert!(mempool.add(forged, &state, Utc::now()).is_err());
+
+        let other_chain = Transaction::transfer(&account::dev_key("Alice"), "abdala-test", 1, account::dev_address("Bob"), Amount(10), Amount::ZERO);
+        assert_eq!(mempool.add(other_chain, &state, Utc::now()).unwrap_err(), "Wrong chain id");
+        assert_eq!(mempool.transactions().count(), 1);
+    }
+
+    #[test]
+    fn test_add_counts_pending_spends() {
+        let state = genesis();
+        let mut mempool = Mempool::new(MempoolConfig::default());
+        mempool.add(dev_transfer("Alice", 0, "Bob", 60, 0), &state, Utc::now()).unwrap();
+        assert_eq!(mempool.add(dev_transfer("Alice", 1, "Bob", 40, 1), &state, Utc::now()).unwrap_err(), "Insufficient balance");
+        mempool.add(dev_transfer("Alice", 1, "Bob", 40, 0), &state, Utc::now()).unwrap();
+        assert_eq!(mempool.add(dev_transfer("Carol", 0, "Bob", 1, 0), &state, Utc::now()).unwrap_err(), "Insufficient balance");
+    }
+
+    #[test]
+    fn test_replace_by_fee() {
+        let state = genesis();
+        let config = MempoolConfig { min_relay_fee_per_kb: Amount(5), ..MempoolConfig::default() };
+        let mut mempool = Mempool::new(config);
+        let original = dev_transfer("Alice", 0, "Bob", 10, 3);
+        mempool.add(original.clone(), &state, Utc::now()).unwrap();
+
+        assert_eq!(mempool.add(dev_transfer("Alice", 0, "Bob", 20, 4), &state, Utc::now()).unwrap_err(), "Replacement fee too low");
+        let replacement = dev_transfer("Alice", 0, "Bob", 20, 6);
+        mempool.add(replacement.clone(), &state, Utc::now()).unwrap();
+        assert_eq!(mempool.transactions().count(), 1);
+        assert!(mempool.contains(&replacement.hash));
+        assert!(!mempool.contains(&original.hash));
+    }
+
+    #[test]
+    fn test_full_pool_evicts_lowest_fee() {
+        let mut state = genesis();
+        state.balances_mut().set_balance(&account::dev_address("Carol"), Amount(100)).unwrap();
+        let config = MempoolConfig { max_transactions: 2, ..MempoolConfig::default() };
+        let mut mempool = Mempool::new(config);
+        let cheap = dev_transfer("Alice", 0, "Bob", 1, 1);
+        mempool.add(cheap.clone(), &state, Utc::now()).unwrap();
+        mempool.add(dev_transfer("Alice", 1, "Bob", 1, 5), &state, Utc::now()).unwrap();
+
+        assert_eq!(mempool.add(dev_transfer("Carol", 0, "Bob", 1, 0), &state, Utc::now()).unwrap_err(), "Mempool full");
+        // Alice's nonce 1 is the only eviction candidate: dropping nonce 0 would strand it.
+        mempool.add(dev_transfer("Carol", 0, "Bob", 1, 9), &state, Utc::now()).unwrap();
+        assert_eq!(mempool.transactions().count(), 2);
+        assert!(mempool.contains(&cheap.hash));
+    }
+
+    #[test]
+    fn test_prune_drops_stale_and_expired() {
+        let mut state = genesis();
+        let mut mempool = Mempool::new(MempoolConfig::default());
+        let now = Utc::now();
+        mempool.add(dev_transfer("Alice", 0, "Bob", 1, 0), &state, now - Duration::hours(2)).unwrap();
+        mempool.add(dev_transfer("Alice", 1, "Bob", 1, 0), &state, now).unwrap();
+        mempool.add(dev_transfer("Alice", 2, "Bob", 1, 0), &state, now).unwrap();
+
+        state.system_mut().increment_nonce(&account::dev_address("Alice")).unwrap();
+        state.system_mut().increment_nonce(&account::dev_address("Alice")).unwrap();
+        mempool.prune(&state, now);
+        assert_eq!(mempool.transactions().count(), 1);
+        assert_eq!(mempool.ready(&state).len(), 1);
+
+        mempool.prune(&state, now + Duration::hours(2));
+        assert_eq!(mempool.transactions().count(), 0);
+    }
+
+    #[test]
+    fn test_prune_drops_unaffordable() {
+        let mut state = genesis();
+        let mut mempool = Mempool::new(MempoolConfig::default());
+        let first = dev_transfer("Alice", 0, "Bob", 60, 0);
+        mempool.add(first.clone(), &state, Utc::now()).unwrap();
+        mempool.add(dev_transfer("Alice", 1, "Bob", 30, 0), &state, Utc::now()).unwrap();
+
+        state.balances_mut().set_balance(&account::dev_address("Alice"), Amount(70)).unwrap();
+        mempool.prune(&state, Utc::now());
+        let hashes: Vec<&String> = mempool.transactions().map(|transaction| &transaction.hash).collect();
+        assert_eq!(hashes, vec![&first.hash]);
+    }
+
+    #[test]
+    fn test_min_relay_fee() {
+        assert_eq!(min_relay_fee(Amount::ZERO, 400), Amount::ZERO);
+        assert_eq!(min_relay_fee(Amount(1), 400), Amount(1));
+        assert_eq!(min_relay_fee(Amount(5), 400), Amount(2));
+        assert_eq!(min_relay_fee(Amount(5), 1000), Amount(5));
+    }
+}
@@ -0,0 +1,87 @@
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use crate::core_client::pow;
+use crate::runtime::Blockchain;
+
+/// Mines in the background whenever the authoring policy says a block is due. A block template is taken from the runtime, its proof is searched on
+/// a blocking thread without holding the runtime lock, and the sealed block is submitted back
+/// through `import_block`. Mining is abandoned as soon as our tip changes, e.g. when a peer's chain
+/// is adopted, since the block would no longer link to it. The proof search is split across
+/// `threads` worker threads.
+pub fn spawn(runtime: Arc<Mutex<Blockchain>>, threads: usize) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            let (template, due_in, tip, wakeup) = {
+                let mut runtime = runtime.lock().await;
+                let template = runtime.should_mine().then(|| runtime.block_template());
+                (template, runtime.time_until_block(), runtime.subscribe_tip(), runtime.miner_wakeup())
+            };
+            let Some(mut block) = template else {
+                // Sleep until the next block is due, or until new transactions or a new tip arrive.
+                match due_in.and_then(|due_in| due_in.to_std().ok()) {
+                    Some(due_in) => { let _ = tokio::time::timeout(due_in, wakeup.notified()).await; }
+                    None => wakeup.notified().await,
+                }
+                continue;
+            };
+
+            let header = block.header();
+            let result = tokio::task::spawn_blocking(move || pow::mine(&header, threads, || tip.has_changed().unwrap_or(true))).await;
+            let result = match result {
+                Ok(result) => result,
+                Err(e) => {
+                    println!("Mining thread failed: {}", e);
+                    continue;
+                }
+            };
+            println!("Mining block {}: {} hashes at {:.0} H/s", block.index, result.hashes, result.hashrate());
+            let Some(proof) = result.proof else {
+                println!("Tip changed, abandoning block {}", block.index);
+                continue;
+            };
+
+            block.proof = proof;
+            let index = block.index;
+            match runtime.lock().await.import_block(block) {
+                Ok(()) => println!("Mined block {}", index),
+                Err(e) => println!("Mined block {} was rejected: {}", index, e),
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use crate::domain::account;
+    use crate::domain::amount::Amount;
+    use crate::domain::transaction::tests::dev_transfer;
+
+    #[tokio::test]
+    async fn test_miner_mines_ready_transactions() {
+        let runtime = Arc::new(Mutex::new(Blockchain::new()));
+        let miner = spawn(runtime.clone(), 2);
+
+        for nonce in 0..5 {
+            let transaction = dev_transfer("Alice", nonce, "Bob", 10, 0);
+            runtime.lock().await.add_transaction(transaction).await.unwrap();
+        }
+
+        let mut mined = false;
+        for _ in 0..600 {
+            if runtime.lock().await.get_chain().len() == 2 {
+                mined = true;
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        miner.abort();
+
+        assert!(mined);
+        let runtime = runtime.lock().await;
+        assert_eq!(runtime.balance(&account::dev_address("Bob")), Amount(50));
+        assert!(runtime.is_chain_valid());
+    }
+}
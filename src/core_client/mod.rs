@@ -1,3 +1,13 @@
 pub (crate) mod rpc;
 pub(crate) mod balance;
-pub(crate) mod system;
\ No newline at end of file
+pub(crate) mod system;
+pub(crate) mod storage;
+pub(crate) mod executive;
+pub(crate) mod chain_spec;
+pub(crate) mod pow;
+pub(crate) mod fork_choice;
+pub(crate) mod miner;
+pub(crate) mod authoring;
+pub(crate) mod mempool;
+pub(crate) mod pallet;
+pub(crate) mod overlay;
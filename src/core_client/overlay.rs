@@ -0,0 +1,225 @@
+use std::collections::BTreeMap;
+use std::ops::Deref;
+
+/// Storage that takes part in storage transactions, see `State::transactional`. Transactions nest:
+/// committing one folds its changes into the enclosing one, rolling it back undoes only its own.
+pub trait Transactional {
+    fn start_transaction(&mut self);
+    fn commit_transaction(&mut self);
+    fn rollback_transaction(&mut self);
+}
+
+/// Map that journals the value each write replaces while a transaction is open, so rolling back
+/// costs as much as the writes it undoes rather than a copy of the whole map. Reads go through
+/// `Deref` to the committed-and-pending items.
+#[derive(Debug, Clone)]
+pub struct StorageMap<K, V> {
+    items: BTreeMap<K, V>,
+    /// One layer per open transaction: the keys written and the values they held before.
+    journal: Vec<Vec<(K, Option<V>)>>,
+}
+
+impl<K: Ord + Clone, V: Clone> StorageMap<K, V> {
+    pub fn new() -> Self {
+        StorageMap { items: BTreeMap::new(), journal: vec![] }
+    }
+
+    pub fn insert(&mut self, key: K, value: V) {
+        let previous = self.items.insert(key.clone(), value);
+        if let Some(layer) = self.journal.last_mut() {
+            layer.push((key, previous));
+        }
+    }
+
+    pub fn to_map(&self) -> BTreeMap<K, V> {
+        self.items.clone()
+    }
+}
+
+impl<K: Ord + Clone, V: Clone> Default for StorageMap<K, V> {
+    fn default() -> Self {
+        StorageMap::new()
+    }
+}
+
+impl<K, V> Deref for StorageMap<K, V> {
+    type Target = BTreeMap<K, V>;
+
+    fn deref(&self) -> &BTreeMap<K, V> {
+        &self.items
+    }
+}
+
+impl<K: Ord + Clone, V: Clone> Transactional for StorageMap<K, V> {
+    fn start_transaction(&mut self) {
+        self.journal.push(vec![]);
+    }
+
+    fn commit_transaction(&mut self) {
+        let layer = self.journal.pop().expect("no storage transaction to commit");
+        if let Some(parent) = self.journal.last_mut() {
+            parent.extend(layer);
+        }
+    }
+
+    fn rollback_transaction(&mut self) {
+        let layer = self.journal.pop().expect("no storage transaction to roll back");
+        for (key, previous) in layer.into_iter().rev() {
+            match previous {
+                Some(value) => self.items.insert(key, value),
+                None => self.items.remove(&key),
+            };
+        }
+    }
+}
+
+/// Single value journalled the same way as `StorageMap`.
+#[derive(Debug, Clone, Default)]
+pub struct StorageValue<V> {
+    value: V,
+    journal: Vec<Vec<V>>,
+}
+
+impl<V: Clone> StorageValue<V> {
+    pub fn new(value: V) -> Self {
+        StorageValue { value, journal: vec![] }
+    }
+
+    pub fn set(&mut self, value: V) {
+        let previous = std::mem::replace(&mut self.value, value);
+        if let Some(layer) = self.journal.last_mut() {
+            layer.push(previous);
+        }
+    }
+}
+
+impl<V> Deref for StorageValue<V> {
+    type Target = V;
+
+    fn deref(&self) -> &V {
+        &self.value
+    }
+}
+
+impl<V: Clone> Transactional for StorageValue<V> {
+    fn start_transaction(&mut self) {
+        self.journal.push(vec![]);
+    }
+
+    fn commit_transaction(&mut self) {
+        let layer = self.journal.pop().expect("no storage transaction to commit");
+        if let Some(parent) = self.journal.last_mut() {
+            parent.extend(layer);
+        }
+    }
+
+    fn rollback_transaction(&mut self) {
+        let layer = self.journal.pop().expect("no storage transaction to roll back");
+        if let Some(original) = layer.into_iter().next() {
+            self.value = original;
+        }
+    }
+}
+
+/// Events deposited by a pallet. Events deposited in a transaction that is rolled back are
+/// dropped with it.
+#[derive(Debug, Clone)]
+pub struct EventLog<E> {
+    events: Vec<E>,
+    /// Number of events when each open transaction started.
+    marks: Vec<usize>,
+}
+
+impl<E> EventLog<E> {
+    pub fn new() -> Self {
+        EventLog { events: vec![], marks: vec![] }
+    }
+
+    pub fn deposit(&mut self, event: E) {
+        self.events.push(event);
+    }
+
+    /// Removes and returns every event deposited so far.
+    pub fn take(&mut self) -> Vec<E> {
+        // Taken events are gone either way; later ones still belong to the open transactions.
+        self.marks.iter_mut().for_each(|mark| *mark = 0);
+        std::mem::take(&mut self.events)
+    }
+}
+
+impl<E> Default for EventLog<E> {
+    fn default() -> Self {
+        EventLog::new()
+    }
+}
+
+impl<E> Transactional for EventLog<E> {
+    fn start_transaction(&mut self) {
+        self.marks.push(self.events.len());
+    }
+
+    fn commit_transaction(&mut self) {
+        self.marks.pop().expect("no storage transaction to commit");
+    }
+
+    fn rollback_transaction(&mut self) {
+        let mark = self.marks.pop().expect("no storage transaction to roll back");
+        self.events.truncate(mark);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_map_rollback_restores_previous_values() {
+        let mut map = StorageMap::new();
+        map.insert("Alice", 10);
+        map.start_transaction();
+        map.insert("Alice", 5);
+        map.insert("Bob", 5);
+        map.insert("Bob", 7);
+        map.rollback_transaction();
+        assert_eq!(map.to_map(), BTreeMap::from([("Alice", 10)]));
+    }
+
+    #[test]
+    fn test_nested_transactions() {
+        let mut map = StorageMap::new();
+        let mut value = StorageValue::new(0);
+        map.start_transaction();
+        value.start_transaction();
+        map.insert("Alice", 1);
+        value.set(1);
+
+        map.start_transaction();
+        value.start_transaction();
+        map.insert("Alice", 2);
+        value.set(2);
+        map.commit_transaction();
+        value.commit_transaction();
+        assert_eq!(map.get("Alice"), Some(&2));
+
+        // The outer rollback also undoes what the inner transaction committed into it.
+        map.rollback_transaction();
+        value.rollback_transaction();
+        assert!(map.is_empty());
+        assert_eq!(*value, 0);
+
+    }
+
+    #[test]
+    fn test_event_log_drops_rolled_back_events() {
+        let mut events = EventLog::new();
+        events.deposit("kept");
+        events.start_transaction();
+        events.deposit("dropped");
+        events.rollback_transaction();
+        events.start_transaction();
+        assert_eq!(events.take(), vec!["kept"]);
+        events.deposit("dropped too");
+        events.rollback_transaction();
+        assert!(events.take().is_empty());
+    }
+}
@@ -0,0 +1,233 @@
+use std::any::Any;
+use std::fmt::Debug;
+use serde::{Deserialize, Serialize};
+use crate::core_client::balance;
+use crate::core_client::chain_spec::ChainSpec;
+use crate::core_client::executive::State;
+use crate::core_client::overlay::Transactional;
+use crate::core_client::system;
+use crate::domain::block::Block;
+use crate::domain::call::RuntimeCall;
+
+/// A runtime module: its own storage, the calls it dispatches, its genesis configuration and the
+/// hooks it runs around every block. Pallets are composed into a runtime with `RuntimeBuilder`.
+pub trait Pallet: Any + Debug + Send + Sync {
+    /// Name calls are routed by, see `RuntimeCall::pallet`.
+    fn name(&self) -> &'static str;
+
+    /// Fills the pallet's storage for the chain described by `spec`.
+    fn genesis(&mut self, _spec: &ChainSpec) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Executes `call` on behalf of `origin`. Only calls routed to this pallet arrive here.
+    fn dispatch(&mut self, _origin: &str, call: &RuntimeCall) -> Result<(), String> {
+        Err(format!("{} cannot dispatch {:?}", self.name(), call))
+    }
+
+    /// Every storage item as `(key, value)` pairs in a deterministic order; the state root
+    /// committed in block headers is computed over them. Events are not storage.
+    fn storage(&self) -> Vec<(String, String)> {
+        vec![]
+    }
+
+    /// Replaces the pallet's storage with `storage`, as listed by `storage`, e.g. from a snapshot.
+    fn restore(&mut self, storage: &[(String, String)]) -> Result<(), String> {
+        match storage.is_empty() {
+            true => Ok(()),
+            false => Err(format!("{} cannot restore its storage", self.name())),
+        }
+    }
+
+    /// Removes and returns the events deposited since the last call.
+    fn take_events(&mut self) -> Vec<RuntimeEvent> {
+        vec![]
+    }
+
+    /// Runs before the coinbase and transactions of every block, when building and importing it.
+    fn on_initialize(&mut self, _block: &BlockContext) {}
+
+    /// Runs after the transactions of every block, when building and importing it.
+    fn on_finalize(&mut self, _block: &BlockContext) {}
+
+    /// Every storage item and event log the pallet writes to, so a storage transaction can commit
+    /// or roll back the pallet's writes without copying its storage.
+    fn journaled(&mut self) -> Vec<&mut dyn Transactional>;
+
+    fn start_transaction(&mut self) {
+        self.journaled().into_iter().for_each(|item| item.start_transaction());
+    }
+
+    fn commit_transaction(&mut self) {
+        self.journaled().into_iter().for_each(|item| item.commit_transaction());
+    }
+
+    fn rollback_transaction(&mut self) {
+        self.journaled().into_iter().for_each(|item| item.rollback_transaction());
+    }
+
+    fn clone_box(&self) -> Box<dyn Pallet>;
+}
+
+impl Clone for Box<dyn Pallet> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+/// What block hooks know about the block being built or imported.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockContext {
+    pub index: usize,
+    pub timestamp: String,
+    /// Beneficiary of the coinbase, who also collects the fees.
+    pub author: Option<String>,
+}
+
+impl BlockContext {
+    pub fn of(block: &Block) -> Self {
+        BlockContext {
+            index: block.index,
+            timestamp: block.timestamp.clone(),
+            author: block.author().map(str::to_string),
+        }
+    }
+}
+
+/// Every event the runtime can deposit, one variant per pallet.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum RuntimeEvent {
+    Balances(balance::Event),
+    System(system::Event),
+}
+
+/// Composes pallets into a runtime. Registration order is the order hooks run in.
+#[derive(Debug, Clone, Default)]
+pub struct RuntimeBuilder {
+    pallets: Vec<Box<dyn Pallet>>,
+}
+
+impl RuntimeBuilder {
+    pub fn new() -> Self {
+        RuntimeBuilder::default()
+    }
+
+    pub fn pallet(mut self, pallet: impl Pallet) -> Self {
+        self.pallets.push(Box::new(pallet));
+        self
+    }
+
+    /// Genesis state of the composed runtime on the chain described by `spec`.
+    pub fn build(mut self, spec: &ChainSpec) -> Result<State, String> {
+        for (position, pallet) in self.pallets.iter().enumerate() {
+            if self.pallets[..position].iter().any(|other| other.name() == pallet.name()) {
+                return Err(format!("Pallet {} is registered twice", pallet.name()));
+            }
+        }
+        for pallet in &mut self.pallets {
+            pallet.genesis(spec)?;
+        }
+        Ok(State::from_pallets(self.pallets))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::core_client::executive::genesis_block;
+    use crate::core_client::overlay::StorageValue;
+    use crate::core_client::system::Pallet as SystemPallet;
+    use crate::domain::account;
+    use crate::domain::amount::Amount;
+    use crate::domain::transaction::Transaction;
+
+    /// Pallet whose hooks count blocks and record their authors.
+    #[derive(Debug, Clone, Default)]
+    pub(crate) struct Counter {
+        pub(crate) blocks: StorageValue<usize>,
+        authors: StorageValue<Vec<Option<String>>>,
+    }
+
+    impl Pallet for Counter {
+        fn name(&self) -> &'static str {
+            "Counter"
+        }
+
+        fn on_initialize(&mut self, block: &BlockContext) {
+            let mut authors = self.authors.to_vec();
+            authors.push(block.author.clone());
+            self.authors.set(authors);
+        }
+
+        fn on_finalize(&mut self, _block: &BlockContext) {
+            self.blocks.set(*self.blocks + 1);
+        }
+
+        fn storage(&self) -> Vec<(String, String)> {
+            vec![("blocks".to_string(), self.blocks.to_string())]
+        }
+
+        fn restore(&mut self, storage: &[(String, String)]) -> Result<(), String> {
+            let blocks = storage.iter().find(|(key, _)| key == "blocks").ok_or("Counter storage has no blocks")?;
+            self.blocks.set(blocks.1.parse().map_err(|_| "Invalid Counter storage value")?);
+            Ok(())
+        }
+
+        fn journaled(&mut self) -> Vec<&mut dyn Transactional> {
+            vec![&mut self.blocks, &mut self.authors]
+        }
+
+        fn clone_box(&self) -> Box<dyn Pallet> {
+            Box::new(self.clone())
+        }
+    }
+
+    #[test]
+    fn test_build_runs_genesis() {
+        let state = RuntimeBuilder::new()
+            .pallet(SystemPallet::new())
+            .pallet(balance::Pallet::new())
+            .build(&ChainSpec::development())
+            .unwrap();
+        assert_eq!(state.balances().balance(&account::dev_address("Alice")), Amount(100));
+    }
+
+    #[test]
+    fn test_build_rejects_duplicate_pallets() {
+        let result = RuntimeBuilder::new().pallet(Counter::default()).pallet(Counter::default()).build(&ChainSpec::development());
+        assert_eq!(result.unwrap_err(), "Pallet Counter is registered twice");
+    }
+
+    #[test]
+    fn test_dispatch_routes_to_registered_pallet() {
+        let mut state = RuntimeBuilder::new()
+            .pallet(SystemPallet::new())
+            .pallet(Counter::default())
+            .build(&ChainSpec::development())
+            .unwrap();
+        let transfer = RuntimeCall::Balances(balance::Call::Transfer { to: account::dev_address("Bob"), amount: Amount(1) });
+        assert_eq!(state.dispatch(&account::dev_address("Alice"), &transfer).unwrap_err(), "Pallet Balances is not part of the runtime");
+        assert_eq!(*state.pallet::<Counter>().unwrap().blocks, 0);
+    }
+
+    #[test]
+    fn test_hooks_run_for_every_block() {
+        let mut state = RuntimeBuilder::new()
+            .pallet(SystemPallet::new())
+            .pallet(balance::Pallet::new())
+            .pallet(Counter::default())
+            .build(&ChainSpec::development())
+            .unwrap();
+        let mut genesis = genesis_block(&ChainSpec::development());
+        genesis.state_root = state.state_root_after(&genesis).unwrap();
+        state.execute_block(&genesis).unwrap();
+        let coinbase = Transaction::coinbase(1, "2025-01-01T00:00:00+00:00".to_string(), account::dev_address("Miner"), Amount(50));
+        let mut block = Block::new(1, "2025-01-01T00:00:00+00:00".to_string(), 0, 16, genesis.hash(), vec![coinbase]);
+        block.state_root = state.state_root_after(&block).unwrap();
+        state.execute_block(&block).unwrap();
+        let counter = state.pallet::<Counter>().unwrap();
+        assert_eq!(*counter.blocks, 2);
+        assert_eq!(*counter.authors, vec![None, Some(account::dev_address("Miner"))]);
+        assert_eq!(*state.system().block_number, 1);
+    }
+}
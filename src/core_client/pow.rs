@@ -0,0 +1,227 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+use chrono::DateTime;
+use crate::core_client::chain_spec::ChainSpec;
+use crate::domain::block::{Block, BlockHeader};
+
+/// Highest difficulty a SHA-256 hash can meet.
+pub const MAX_DIFFICULTY: u32 = 256;
+
+pub fn leading_zero_bits(hash: &[u8]) -> u32 {
+    let mut bits = 0;
+    for byte in hash {
+        bits += byte.leading_zeros();
+        if *byte != 0 { break; }
+    }
+    bits
+}
+
+/// A hash meets `difficulty` when it starts with at least that many zero bits.
+pub fn meets_difficulty(hash: &[u8], difficulty: u32) -> bool {
+    leading_zero_bits(hash) >= difficulty
+}
+
+/// Whether the header hash meets the difficulty the header declares. The hash covers the parent
+/// hash and transactions root, so a proof only ever seals the one block it was found for.
+pub fn valid_seal(header: &BlockHeader) -> bool {
+    meets_difficulty(&header.digest(), header.difficulty)
+}
+
+/// Attempts a worker makes between two checks of whether it should stop.
+const STOP_CHECK_INTERVAL: u64 = 1024;
+
+/// Outcome of a mining run: the proof found, if any, and how much hashing it took.
+#[derive(Debug, Clone, Copy)]
+pub struct MiningResult {
+    pub proof: Option<u64>,
+    pub hashes: u64,
+    pub elapsed: Duration,
+}
+
+impl MiningResult {
+    /// Hashes per second over the run.
+    pub fn hashrate(&self) -> f64 {
+        let seconds = self.elapsed.as_secs_f64();
+        if seconds > 0.0 { self.hashes as f64 / seconds } else { 0.0 }
+    }
+}
+
+/// Searches for the proof (nonce) that seals `header` on the calling thread, starting from 0.
+pub fn proof_of_work(header: &BlockHeader) -> u64 {
+    mine(header, 1, || false).proof.expect("No proof seals the header")
+}
+
+/// Searches for a proof with `threads` workers. Worker `i` tries `i`, `i + threads`,
+/// `i + 2 * threads`, ... so the nonce space is split without overlap. All workers stop as soon
+/// as one finds a proof or `should_stop` returns true, in which case `proof` is `None`.
+pub fn mine(header: &BlockHeader, threads: usize, should_stop: impl Fn() -> bool + Sync) -> MiningResult {
+    let threads = threads.max(1);
+    let started = Instant::now();
+    let done = AtomicBool::new(false);
+    let hashes = AtomicU64::new(0);
+    let found = Mutex::new(None);
+
+    thread::scope(|scope| {
+        for worker in 0..threads {
+            let mut header = header.clone();
+            let (done, hashes, found, should_stop) = (&done, &hashes, &found, &should_stop);
+            scope.spawn(move || {
+                let mut attempts = 0;
+                for proof in (worker as u64..=u64::MAX).step_by(threads) {
+                    if attempts % STOP_CHECK_INTERVAL == 0 && (done.load(Ordering::Relaxed) || should_stop()) {
+                        break;
+                    }
+                    attempts += 1;
+                    header.proof = proof;
+                    if valid_seal(&header) {
+                        found.lock().unwrap().get_or_insert(proof);
+                        done.store(true, Ordering::Relaxed);
+                        break;
+                    }
+                }
+                hashes.fetch_add(attempts, Ordering::Relaxed);
+            });
+        }
+    });
+
+    MiningResult {
+        proof: found.into_inner().unwrap(),
+        hashes: hashes.into_inner(),
+        elapsed: started.elapsed(),
+    }
+}
+
+/// Difficulty the block after `chain` must declare.
+///
+/// Every `difficulty_adjustment_interval` blocks the time the last interval took is compared with
+/// the target: one bit harder (twice the work) when blocks came in more than twice as fast, one bit
+/// easier when they took more than twice as long. Windows that start at genesis are skipped since
+/// the genesis timestamp is fixed by the chain spec and says nothing about the network's hashrate.
+pub fn next_difficulty(chain: &[Block], spec: &ChainSpec) -> u32 {
+    let Some(parent) = chain.last() else { return spec.initial_difficulty; };
+    let interval = spec.difficulty_adjustment_interval;
+    let height = chain.len();
+    if !height.is_multiple_of(interval) || height <= interval + 1 {
+        return parent.difficulty;
+    }
+
+    let window_start = &chain[height - 1 - interval];
+    let (Ok(start), Ok(end)) = (DateTime::parse_from_rfc3339(&window_start.timestamp), DateTime::parse_from_rfc3339(&parent.timestamp)) else {
+        return parent.difficulty;
+    };
+    let actual = (end - start).num_seconds().max(0) as u64;
+    let expected = spec.target_block_time_secs * interval as u64;
+
+    if actual.saturating_mul(2) < expected {
+        (parent.difficulty + 1).min(MAX_DIFFICULTY)
+    } else if actual > expected.saturating_mul(2) {
+        parent.difficulty.saturating_sub(1).max(spec.min_difficulty)
+    } else {
+        parent.difficulty
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core_client::executive::genesis_block;
+    use chrono::{Duration, TimeZone, Utc};
+
+    #[test]
+    fn test_leading_zero_bits() {
+        assert_eq!(leading_zero_bits(&[0xff]), 0);
+        assert_eq!(leading_zero_bits(&[0x00, 0x0f]), 12);
+        assert_eq!(leading_zero_bits(&[0x00, 0x00]), 16);
+        assert!(meets_difficulty(&[0x00, 0x01], 15));
+        assert!(!meets_difficulty(&[0x00, 0x01], 16));
+    }
+
+    #[test]
+    fn test_proof_of_work_seals_block_contents() {
+        let mut header = genesis_block(&ChainSpec::development()).header();
+        header.index = 1;
+        header.previous_hash = "0".repeat(64);
+        header.proof = proof_of_work(&header);
+        assert!(valid_seal(&header));
+
+        let mut other_transactions = header.clone();
+        other_transactions.transactions_root = "1".repeat(64);
+        assert!(!valid_seal(&other_transactions));
+
+        let mut other_parent = header.clone();
+        other_parent.previous_hash = "1".repeat(64);
+        assert!(!valid_seal(&other_parent));
+    }
+
+    #[test]
+    fn test_mine_stops_when_asked() {
+        let mut header = genesis_block(&ChainSpec::development()).header();
+        header.difficulty = MAX_DIFFICULTY;
+        let result = mine(&header, 4, || true);
+        assert_eq!(result.proof, None);
+        assert_eq!(result.hashes, 0);
+    }
+
+    #[test]
+    fn test_mine_with_several_threads() {
+        let mut header = genesis_block(&ChainSpec::development()).header();
+        header.index = 1;
+        header.previous_hash = "0".repeat(64);
+
+        let single = mine(&header, 1, || false);
+        assert_eq!(single.proof, Some(proof_of_work(&header)));
+
+        let parallel = mine(&header, 4, || false);
+        header.proof = parallel.proof.unwrap();
+        assert!(valid_seal(&header));
+        assert!(parallel.hashes > 0);
+        assert!(parallel.hashrate() > 0.0);
+    }
+
+    /// Chain of `count` blocks after genesis at `spacing` seconds, all declaring `difficulty`.
+    fn chain(count: usize, spacing: i64, difficulty: u32) -> Vec<Block> {
+        let spec = ChainSpec::development();
+        let start = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap();
+        let mut chain = vec![genesis_block(&spec)];
+        for index in 1..=count {
+            let timestamp = (start + Duration::seconds(spacing * index as i64)).to_rfc3339();
+            chain.push(Block::new(index, timestamp, 0, difficulty, "0".to_string(), vec![]));
+        }
+        chain
+    }
+
+    #[test]
+    fn test_next_difficulty_between_retargets() {
+        let spec = ChainSpec::development();
+        assert_eq!(next_difficulty(&[], &spec), spec.initial_difficulty);
+        assert_eq!(next_difficulty(&chain(3, 1, 18), &spec), 18);
+    }
+
+    #[test]
+    fn test_next_difficulty_retargets() {
+        let spec = ChainSpec::development();
+        let interval = spec.difficulty_adjustment_interval;
+        let target = spec.target_block_time_secs as i64;
+
+        let fast = chain(2 * interval - 1, 1, 18);
+        assert_eq!(next_difficulty(&fast, &spec), 19);
+
+        let slow = chain(2 * interval - 1, target * 3, 18);
+        assert_eq!(next_difficulty(&slow, &spec), 17);
+
+        let on_target = chain(2 * interval - 1, target, 18);
+        assert_eq!(next_difficulty(&on_target, &spec), 18);
+
+        let at_minimum = chain(2 * interval - 1, target * 3, spec.min_difficulty);
+        assert_eq!(next_difficulty(&at_minimum, &spec), spec.min_difficulty);
+    }
+
+    #[test]
+    fn test_next_difficulty_skips_genesis_window() {
+        let spec = ChainSpec::development();
+        let first_window = chain(spec.difficulty_adjustment_interval - 1, 1, 18);
+        assert_eq!(next_difficulty(&first_window, &spec), 18);
+    }
+}
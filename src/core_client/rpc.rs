@@ -1,11 +1,21 @@
 use crate::runtime::{Blockchain};
 use rocket::{get, post, routes, serde::json::Json, State};
+use rocket::http::Status;
 use rocket::serde::json::serde_json::json;
+use std::sync::Arc;
 use tokio::sync::Mutex;
 use crate::domain::transaction::Transaction;
+use crate::domain::amount::Amount;
+use crate::domain::account;
+use crate::core_client::authoring::AuthoringPolicy;
+use crate::core_client::chain_spec::ChainSpec;
+use crate::core_client::miner;
+use crate::core_client::mempool::MempoolConfig;
+
+const DEFAULT_DATA_DIR: &str = "data";
 
 #[get("/get_chain")]
-async fn get_chain(runtime: &State<Mutex<Blockchain>>) -> Json<serde_json::Value> {
+async fn get_chain(runtime: &State<Arc<Mutex<Blockchain>>>) -> Json<serde_json::Value> {
     let runtime = runtime.lock().await;
     Json(json!({
         "chain": runtime.get_chain(),
@@ -14,7 +24,7 @@ async fn get_chain(runtime: &State<Mutex<Blockchain>>) -> Json<serde_json::Value
 }
 
 #[get("/is_valid")]
-async fn is_valid(runtime: &State<Mutex<Blockchain>>) -> Json<serde_json::Value> {
+async fn is_valid(runtime: &State<Arc<Mutex<Blockchain>>>) -> Json<serde_json::Value> {
     let runtime = runtime.lock().await;
     let is_valid = runtime.is_chain_valid();
     if is_valid {
@@ -25,16 +35,18 @@ async fn is_valid(runtime: &State<Mutex<Blockchain>>) -> Json<serde_json::Value>
 }
 
 #[post("/add_transaction", format = "json", data = "<transaction>")]
-async fn add_transaction(runtime: &State<Mutex<Blockchain>>, transaction: Json<Transaction>) -> Json<serde_json::Value> {
+async fn add_transaction(runtime: &State<Arc<Mutex<Blockchain>>>, transaction: Json<Transaction>) -> (Status, Json<serde_json::Value>) {
     let transaction = transaction.into_inner();
-    let transaction = Transaction::new(transaction.sender, transaction.to, transaction.amount, transaction.message);
+    let hash = transaction.hash.clone();
     let mut runtime = runtime.lock().await;
-    runtime.add_transaction(transaction).await;
-    Json(json!({"message": "This transaction will be added to the next block"}))
+    match runtime.add_transaction(transaction).await {
+        Ok(()) => (Status::Ok, Json(json!({"message": "This transaction will be added to the next block", "hash": hash}))),
+        Err(e) => (Status::BadRequest, Json(json!({"message": "Transaction rejected", "error": e}))),
+    }
 }
 
 #[post("/connect_node", format = "json", data = "<nodes>")]
-async fn connect_node(runtime: &State<Mutex<Blockchain>>, nodes: Json<serde_json::Value>) -> Json<serde_json::Value> {
+async fn connect_node(runtime: &State<Arc<Mutex<Blockchain>>>, nodes: Json<serde_json::Value>) -> Json<serde_json::Value> {
     let nodes = nodes.get("nodes").and_then(|n| n.as_array()).cloned().unwrap_or_default();
     if nodes.is_empty() {
         return Json(json!("Empty"));
@@ -52,16 +64,17 @@ async fn connect_node(runtime: &State<Mutex<Blockchain>>, nodes: Json<serde_json
 }
 
 #[get("/balance?<address>")]
-async fn balance(runtime: &State<Mutex<Blockchain>>, address: &str) -> Json<serde_json::Value> {
+async fn balance(runtime: &State<Arc<Mutex<Blockchain>>>, address: &str) -> Json<serde_json::Value> {
     let runtime = runtime.lock().await;
     let balance = runtime.balance(address);
     Json(json!({
-        "balance": balance
+        "balance": balance,
+        "formatted": balance.format(runtime.chain_spec().decimals)
     }))
 }
 
 #[get("/get_nonce?<address>")]
-async fn get_nonce(runtime: &State<Mutex<Blockchain>>, address: &str) -> Json<serde_json::Value> {
+async fn get_nonce(runtime: &State<Arc<Mutex<Blockchain>>>, address: &str) -> Json<serde_json::Value> {
     let runtime = runtime.lock().await;
     let nonce = runtime.get_nonce(address);
     Json(json!({
@@ -70,7 +83,7 @@ async fn get_nonce(runtime: &State<Mutex<Blockchain>>, address: &str) -> Json<se
 }
 
 #[get("/get_all_nonce")]
-async fn get_all_nonce(runtime: &State<Mutex<Blockchain>>) -> Json<serde_json::Value> {
+async fn get_all_nonce(runtime: &State<Arc<Mutex<Blockchain>>>) -> Json<serde_json::Value> {
     let runtime = runtime.lock().await;
     let nonce = runtime.get_all_nonce();
     Json(json!({
@@ -79,19 +92,87 @@ async fn get_all_nonce(runtime: &State<Mutex<Blockchain>>) -> Json<serde_json::V
 }
 
 #[get("/get_all_balance")]
-async fn get_all_balance(runtime: &State<Mutex<Blockchain>>) -> Json<serde_json::Value> {
+async fn get_all_balance(runtime: &State<Arc<Mutex<Blockchain>>>) -> Json<serde_json::Value> {
     let runtime = runtime.lock().await;
     let balance = runtime.get_all_balance();
     Json(json!({
-        "balance": balance
+        "balance": balance,
+        "total_issuance": runtime.total_issuance()
     }))
 }
 
-pub async fn start_server() -> Result<(), rocket::Error> {
-    let runtime = Blockchain::new();
-    rocket::build()
-        .manage(Mutex::new(runtime))
-        .mount("/", routes![get_chain, is_valid, add_transaction, connect_node, balance, get_nonce, get_all_nonce, get_all_balance])
+#[get("/tx_proof?<hash>")]
+async fn tx_proof(runtime: &State<Arc<Mutex<Blockchain>>>, hash: &str) -> (Status, Json<serde_json::Value>) {
+    let runtime = runtime.lock().await;
+    match runtime.transaction_proof(hash) {
+        Some(proof) => (Status::Ok, Json(json!(proof))),
+        None => (Status::NotFound, Json(json!({"message": "Transaction not found in any block"}))),
+    }
+}
+
+#[get("/receipt?<hash>")]
+async fn receipt(runtime: &State<Arc<Mutex<Blockchain>>>, hash: &str) -> (Status, Json<serde_json::Value>) {
+    let runtime = runtime.lock().await;
+    match runtime.transaction_receipt(hash) {
+        Some(receipt) => (Status::Ok, Json(json!(receipt))),
+        None => (Status::NotFound, Json(json!({"message": "Receipt not found"}))),
+    }
+}
+
+#[get("/chain_spec")]
+async fn chain_spec(runtime: &State<Arc<Mutex<Blockchain>>>) -> Json<serde_json::Value> {
+    let runtime = runtime.lock().await;
+    Json(json!(runtime.chain_spec()))
+}
+
+/// Starts the node. `data_dir` and `chain_spec` from the command line win over the same keys in
+/// Rocket.toml. Without a chain spec file the development spec is used.
+pub async fn start_server(data_dir: Option<String>, chain_spec_path: Option<String>) -> Result<(), rocket::Error> {
+    let rocket = rocket::build();
+    let data_dir = data_dir
+        .or_else(|| rocket.figment().extract_inner::<String>("data_dir").ok())
+        .unwrap_or_else(|| DEFAULT_DATA_DIR.to_string());
+    let spec = match chain_spec_path.or_else(|| rocket.figment().extract_inner::<String>("chain_spec").ok()) {
+        Some(path) => ChainSpec::load(&path).unwrap_or_else(|e| panic!("{}", e)),
+        None => ChainSpec::development(),
+    };
+    println!("Chain spec: {}", spec.chain_id);
+    let mut runtime = Blockchain::open(&data_dir, spec).unwrap_or_else(|e| panic!("Could not open chain data in {}: {}", data_dir, e));
+    let cross_check = rocket.figment().extract_inner::<bool>("cross_check_peer_state").unwrap_or(false);
+    runtime.set_cross_check_peer_state(cross_check);
+    let mut authoring = AuthoringPolicy::default();
+    if let Ok(seconds) = rocket.figment().extract_inner::<i64>("block_interval_secs") {
+        authoring.block_interval = chrono::Duration::seconds(seconds);
+    }
+    if let Ok(empty_blocks) = rocket.figment().extract_inner::<bool>("empty_blocks") {
+        authoring.empty_blocks = empty_blocks;
+    }
+    if let Ok(address) = rocket.figment().extract_inner::<String>("miner_address") {
+        account::validate_address(&address).unwrap_or_else(|e| panic!("Invalid miner_address {}: {}", address, e));
+        authoring.beneficiary = Some(address);
+    }
+    runtime.set_authoring_policy(authoring);
+    let mut mempool = MempoolConfig::default();
+    if let Ok(max_transactions) = rocket.figment().extract_inner::<usize>("mempool_max_transactions") {
+        mempool.max_transactions = max_transactions;
+    }
+    if let Ok(seconds) = rocket.figment().extract_inner::<i64>("mempool_max_age_secs") {
+        mempool.max_age = chrono::Duration::seconds(seconds);
+    }
+    if let Ok(fee) = rocket.figment().extract_inner::<String>("min_relay_fee_per_kb") {
+        mempool.min_relay_fee_per_kb = Amount::parse(&fee, runtime.chain_spec().decimals)
+            .unwrap_or_else(|e| panic!("Invalid min_relay_fee_per_kb {}: {}", fee, e));
+    }
+    runtime.set_mempool_config(mempool);
+    println!("Chain data directory: {}", data_dir);
+    let mining_threads = rocket.figment().extract_inner::<usize>("mining_threads")
+        .unwrap_or_else(|_| std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1));
+    println!("Mining threads: {}", mining_threads);
+    let runtime = Arc::new(Mutex::new(runtime));
+    miner::spawn(runtime.clone(), mining_threads);
+    rocket
+        .manage(runtime)
+        .mount("/", routes![get_chain, is_valid, add_transaction, connect_node, balance, get_nonce, get_all_nonce, get_all_balance, tx_proof, receipt, chain_spec])
         .launch()
         .await?;
 
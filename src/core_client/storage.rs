@@ -0,0 +1,242 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use crate::core_client::executive::Receipt;
+use crate::domain::block::Block;
+
+const BLOCKS_FILE: &str = "blocks.log";
+const RECEIPTS_FILE: &str = "receipts.log";
+const STATE_FILE: &str = "state.json";
+
+/// Snapshot of the pallet storage as of a block in the log, so reopening the chain only has to
+/// execute the blocks after it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StoredState {
+    /// Number of blocks up to and including the one the snapshot was taken at.
+    pub block_count: usize,
+    pub block_hash: String,
+    /// Storage of every pallet by pallet name, see `State::storage`.
+    pub storage: BTreeMap<String, Vec<(String, String)>>,
+}
+
+/// On-disk storage: append-only logs with one JSON line per block, holding the blocks and the
+/// receipts of their transactions, plus a state snapshot that is replaced atomically (write to a
+/// temporary file, fsync, rename).
+#[derive(Debug)]
+pub struct Storage {
+    dir: PathBuf,
+}
+
+impl Storage {
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self, String> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir).map_err(|e| format!("Could not create data directory {}: {}", dir.display(), e))?;
+        Ok(Storage { dir })
+    }
+
+    fn blocks_path(&self) -> PathBuf {
+        self.dir.join(BLOCKS_FILE)
+    }
+
+    fn receipts_path(&self) -> PathBuf {
+        self.dir.join(RECEIPTS_FILE)
+    }
+
+    fn state_path(&self) -> PathBuf {
+        self.dir.join(STATE_FILE)
+    }
+
+    pub fn load_blocks(&self) -> Result<Vec<Block>, String> {
+        self.load_log(&self.blocks_path(), "block log")
+    }
+
+    pub fn append_block(&self, block: &Block) -> Result<(), String> {
+        self.append_log(&self.blocks_path(), "block log", block)
+    }
+
+    /// Rewrites the whole log, used when the chain is replaced by a peer's chain.
+    pub fn replace_blocks(&self, blocks: &[Block]) -> Result<(), String> {
+        self.replace_log(&self.blocks_path(), blocks)
+    }
+
+    /// Receipts of each block in the receipt log, by block index.
+    pub fn load_receipts(&self) -> Result<Vec<Vec<Receipt>>, String> {
+        self.load_log(&self.receipts_path(), "receipt log")
+    }
+
+    /// Appends the receipts of the next block, after the block itself was appended.
+    pub fn append_receipts(&self, receipts: &[Receipt]) -> Result<(), String> {
+        self.append_log(&self.receipts_path(), "receipt log", receipts)
+    }
+
+    pub fn replace_receipts(&self, receipts: &[Vec<Receipt>]) -> Result<(), String> {
+        self.replace_log(&self.receipts_path(), receipts)
+    }
+
+    /// Reads every complete entry in the log at `path`. A torn last line left by a crash
+    /// mid-append is cut off so the next append starts on a clean line.
+    fn load_log<T: DeserializeOwned>(&self, path: &Path, name: &str) -> Result<Vec<T>, String> {
+        let contents = match fs::read(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(format!("Could not read {}: {}", name, e)),
+        };
+
+        let mut entries = vec![];
+        let mut valid_len = 0;
+        for line in contents.split_inclusive(|byte| *byte == b'\n') {
+            // Only the last line can be missing its newline, and only after an interrupted append.
+            if !line.ends_with(b"\n") { break; }
+            let is_last = valid_len + line.len() == contents.len();
+            match serde_json::from_slice::<T>(line) {
+                Ok(entry) => entries.push(entry),
+                Err(_) if is_last => break,
+                Err(e) => return Err(format!("Corrupted {} at block {}: {}", name, entries.len(), e)),
+            }
+            valid_len += line.len();
+        }
+
+        if valid_len < contents.len() {
+            let file = OpenOptions::new().write(true).open(path)
+                .map_err(|e| format!("Could not open {}: {}", name, e))?;
+            file.set_len(valid_len as u64).map_err(|e| format!("Could not truncate {}: {}", name, e))?;
+            file.sync_all().map_err(|e| format!("Could not sync {}: {}", name, e))?;
+        }
+        Ok(entries)
+    }
+
+    fn append_log<T: Serialize + ?Sized>(&self, path: &Path, name: &str, entry: &T) -> Result<(), String> {
+        let mut line = serde_json::to_vec(entry).map_err(|e| e.to_string())?;
+        line.push(b'\n');
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("Could not open {}: {}", name, e))?;
+        file.write_all(&line).map_err(|e| format!("Could not append to {}: {}", name, e))?;
+        file.sync_all().map_err(|e| format!("Could not sync {}: {}", name, e))
+    }
+
+    fn replace_log<T: Serialize>(&self, path: &Path, entries: &[T]) -> Result<(), String> {
+        let mut contents = vec![];
+        for entry in entries {
+            contents.extend(serde_json::to_vec(entry).map_err(|e| e.to_string())?);
+            contents.push(b'\n');
+        }
+        self.write_atomically(path, &contents)
+    }
+
+    pub fn load_state(&self) -> Result<Option<StoredState>, String> {
+        match fs::read(self.state_path()) {
+            Ok(bytes) => serde_json::from_slice(&bytes).map(Some).map_err(|e| format!("Corrupted state file: {}", e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(format!("Could not read state file: {}", e)),
+        }
+    }
+
+    pub fn save_state(&self, state: &StoredState) -> Result<(), String> {
+        let contents = serde_json::to_vec_pretty(state).map_err(|e| e.to_string())?;
+        self.write_atomically(&self.state_path(), &contents)
+    }
+
+    fn write_atomically(&self, path: &Path, contents: &[u8]) -> Result<(), String> {
+        let temp_path = path.with_extension("tmp");
+        let mut file = File::create(&temp_path).map_err(|e| format!("Could not create {}: {}", temp_path.display(), e))?;
+        file.write_all(contents).map_err(|e| format!("Could not write {}: {}", temp_path.display(), e))?;
+        file.sync_all().map_err(|e| format!("Could not sync {}: {}", temp_path.display(), e))?;
+        fs::rename(&temp_path, path).map_err(|e| format!("Could not replace {}: {}", path.display(), e))?;
+        if let Ok(dir) = File::open(&self.dir) {
+            let _ = dir.sync_all();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::core_client::executive::ReceiptStatus;
+
+    /// Fresh, empty directory under the system temp dir for a single test.
+    pub(crate) fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("abdala-chain-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn block(index: usize) -> Block {
+        Block::new(index, "2021-08-01T00:00:00".to_string(), index as u64, 16, "0".to_string(), vec![])
+    }
+
+    #[test]
+    fn test_append_and_load_blocks() {
+        let storage = Storage::open(temp_dir("append")).unwrap();
+        assert!(storage.load_blocks().unwrap().is_empty());
+
+        storage.append_block(&block(0)).unwrap();
+        storage.append_block(&block(1)).unwrap();
+        let blocks = storage.load_blocks().unwrap();
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[1].hash(), block(1).hash());
+    }
+
+    #[test]
+    fn test_load_ignores_torn_last_block() {
+        let storage = Storage::open(temp_dir("torn")).unwrap();
+        storage.append_block(&block(0)).unwrap();
+        let mut file = OpenOptions::new().append(true).open(storage.blocks_path()).unwrap();
+        file.write_all(b"{\"index\":1,\"timest").unwrap();
+
+        let blocks = storage.load_blocks().unwrap();
+        assert_eq!(blocks.len(), 1);
+
+        storage.append_block(&block(1)).unwrap();
+        let blocks = storage.load_blocks().unwrap();
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[1].index, 1);
+    }
+
+    #[test]
+    fn test_replace_blocks() {
+        let storage = Storage::open(temp_dir("replace")).unwrap();
+        storage.append_block(&block(0)).unwrap();
+        storage.replace_blocks(&[block(5), block(6), block(7)]).unwrap();
+        let blocks = storage.load_blocks().unwrap();
+        assert_eq!(blocks.len(), 3);
+        assert_eq!(blocks[0].index, 5);
+    }
+
+    #[test]
+    fn test_append_and_load_receipts() {
+        let storage = Storage::open(temp_dir("receipts")).unwrap();
+        assert!(storage.load_receipts().unwrap().is_empty());
+
+        let receipt = Receipt {
+            transaction_hash: "ab".repeat(32),
+            block_index: 1,
+            block_hash: "cd".repeat(32),
+            status: ReceiptStatus::Success,
+            events: vec![],
+        };
+        storage.append_receipts(&[]).unwrap();
+        storage.append_receipts(std::slice::from_ref(&receipt)).unwrap();
+        assert_eq!(storage.load_receipts().unwrap(), vec![vec![], vec![receipt]]);
+
+        storage.replace_receipts(&[vec![]]).unwrap();
+        assert_eq!(storage.load_receipts().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_save_and_load_state() {
+        let storage = Storage::open(temp_dir("state")).unwrap();
+        assert!(storage.load_state().unwrap().is_none());
+
+        let mut state = StoredState { block_count: 1, block_hash: "0".repeat(64), ..Default::default() };
+        state.storage.insert("System".to_string(), vec![("nonce/Alice".to_string(), "3".to_string())]);
+        storage.save_state(&state).unwrap();
+        assert_eq!(storage.load_state().unwrap(), Some(state));
+    }
+}
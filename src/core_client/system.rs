@@ -1,17 +1,44 @@
-use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+use crate::core_client::chain_spec::ChainSpec;
+use crate::core_client::overlay::{EventLog, StorageMap, StorageValue, Transactional};
+use crate::core_client::pallet::{self, BlockContext, RuntimeEvent};
+use crate::domain::call::RuntimeCall;
 
-#[derive(Debug)]
+pub use crate::domain::call::SystemCall as Call;
+
+/// Events deposited by the system pallet.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Event {
+    NonceIncremented { account: String, nonce: u64 },
+    Remarked { sender: String },
+    /// The transaction was included and paid its fee, but its call failed and was reverted.
+    ExtrinsicFailed { reason: String },
+}
+
+#[derive(Debug, Clone)]
 pub struct Pallet {
-    pub nonce: BTreeMap<String, u64>,
+    /// Chain transactions must be signed for, from the chain spec.
+    pub chain_id: String,
+    pub nonce: StorageMap<String, u64>,
+    /// Index of the block being executed, or of the last one once it is done.
+    pub block_number: StorageValue<usize>,
+    events: EventLog<Event>,
 }
 
 impl Pallet {
     pub fn new() -> Self {
         Pallet {
-            nonce: BTreeMap::new(),
+            chain_id: String::new(),
+            nonce: StorageMap::new(),
+            block_number: StorageValue::new(0),
+            events: EventLog::new(),
         }
     }
 
+    pub fn deposit_event(&mut self, event: Event) {
+        self.events.deposit(event);
+    }
+
     pub fn get_nonce(&self, address: &str) -> u64 {
         match self.nonce.get(address) {
             Some(nonce) => *nonce,
@@ -24,9 +51,11 @@ impl Pallet {
         let new_nonce = nonce.checked_add(1);
         if new_nonce.is_none() { return Err("Not possible increment nonce".to_string()); }
         self.nonce.insert(address.to_string(), new_nonce.unwrap());
+        self.deposit_event(Event::NonceIncremented { account: address.to_string(), nonce: new_nonce.unwrap() });
         Ok(())
     }
 
+    #[allow(dead_code)]
     pub fn decrement_nonce(&mut self, address: &str) -> Result<(), String> {
         let nonce = self.get_nonce(address);
         let new_nonce = nonce.checked_sub(1);
@@ -36,8 +65,68 @@ impl Pallet {
     }
 }
 
-#[cfg(test)]
+impl pallet::Pallet for Pallet {
+    fn name(&self) -> &'static str {
+        "System"
+    }
+
+    fn genesis(&mut self, spec: &ChainSpec) -> Result<(), String> {
+        self.chain_id = spec.chain_id.clone();
+        Ok(())
+    }
+
+    fn on_initialize(&mut self, block: &BlockContext) {
+        self.block_number.set(block.index);
+    }
+
+    fn dispatch(&mut self, origin: &str, call: &RuntimeCall) -> Result<(), String> {
+        match call {
+            RuntimeCall::System(Call::Remark { .. }) => {
+                self.deposit_event(Event::Remarked { sender: origin.to_string() });
+                Ok(())
+            }
+            _ => Err(format!("System cannot dispatch {:?}", call)),
+        }
+    }
 
+    fn storage(&self) -> Vec<(String, String)> {
+        let mut storage: Vec<(String, String)> = self.nonce.iter()
+            .map(|(address, nonce)| (format!("nonce/{}", address), nonce.to_string()))
+            .collect();
+        storage.push(("chain_id".to_string(), self.chain_id.clone()));
+        storage.push(("block_number".to_string(), self.block_number.to_string()));
+        storage
+    }
+
+    fn restore(&mut self, storage: &[(String, String)]) -> Result<(), String> {
+        let mut restored = Pallet::new();
+        for (key, value) in storage {
+            let invalid = || format!("Invalid System storage value {}", value);
+            match key.strip_prefix("nonce/") {
+                Some(address) => restored.nonce.insert(address.to_string(), value.parse().map_err(|_| invalid())?),
+                None if key == "chain_id" => restored.chain_id = value.clone(),
+                None if key == "block_number" => restored.block_number.set(value.parse().map_err(|_| invalid())?),
+                None => return Err(format!("Unknown System storage item {}", key)),
+            }
+        }
+        *self = restored;
+        Ok(())
+    }
+
+    fn take_events(&mut self) -> Vec<RuntimeEvent> {
+        self.events.take().into_iter().map(RuntimeEvent::System).collect()
+    }
+
+    fn journaled(&mut self) -> Vec<&mut dyn Transactional> {
+        vec![&mut self.nonce, &mut self.block_number, &mut self.events]
+    }
+
+    fn clone_box(&self) -> Box<dyn pallet::Pallet> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
 #[test]
 fn test_balance_new() {
     let pallet = Pallet::new();
@@ -49,6 +138,6 @@ fn test_increment_nonce() {
     let mut pallet = Pallet::new();
     let address = "0x123";
     let result = pallet.increment_nonce(address);
-    assert_eq!(result.is_ok(), true);
+    assert!(result.is_ok());
     assert_eq!(pallet.get_nonce(address), 1);
 }
\ No newline at end of file
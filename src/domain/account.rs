@@ -0,0 +1,109 @@
+use digest::Digest;
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use sha2::Sha256;
+
+/// Derives the account address from a public key: the first 20 bytes of its SHA-256, hex encoded.
+pub fn address(public_key: &VerifyingKey) -> String {
+    let digest = Sha256::digest(public_key.as_bytes());
+    hex::encode(&digest[..20])
+}
+
+/// Checks that `address` has the shape `address` produces: 40 lowercase hex characters.
+pub fn validate_address(address: &str) -> Result<(), String> {
+    if address.len() != 40 || !address.bytes().all(|byte| matches!(byte, b'0'..=b'9' | b'a'..=b'f')) {
+        return Err("Address must be 40 lowercase hex characters".to_string());
+    }
+    Ok(())
+}
+
+/// Chain id of the development chain spec, which dev keys sign for in tests.
+pub const DEV_CHAIN_ID: &str = "abdala-dev";
+
+/// Deterministic development key, derived from a name such as "Alice" or "Bob".
+/// Only meant for genesis accounts and tests, never for real funds.
+pub fn dev_key(name: &str) -> SigningKey {
+    let seed: [u8; 32] = Sha256::digest(name.as_bytes()).into();
+    SigningKey::from_bytes(&seed)
+}
+
+pub fn dev_address(name: &str) -> String {
+    address(&dev_key(name).verifying_key())
+}
+
+pub fn sign(key: &SigningKey, message: &[u8]) -> String {
+    hex::encode(key.sign(message).to_bytes())
+}
+
+pub fn decode_public_key(public_key: &str) -> Result<VerifyingKey, String> {
+    let bytes: [u8; 32] = hex::decode(public_key)
+        .map_err(|_| "Invalid public key encoding".to_string())?
+        .try_into()
+        .map_err(|_| "Invalid public key length".to_string())?;
+    VerifyingKey::from_bytes(&bytes).map_err(|_| "Invalid public key".to_string())
+}
+
+/// Checks that `signature` was produced over `message` by `public_key` and that the key owns `sender`.
+pub fn verify(sender: &str, public_key: &str, message: &[u8], signature: &str) -> Result<(), String> {
+    let public_key = decode_public_key(public_key)?;
+    if address(&public_key) != sender {
+        return Err("Public key does not match sender".to_string());
+    }
+
+    let bytes: [u8; 64] = hex::decode(signature)
+        .map_err(|_| "Invalid signature encoding".to_string())?
+        .try_into()
+        .map_err(|_| "Invalid signature length".to_string())?;
+    let signature = Signature::from_bytes(&bytes);
+    public_key
+        .verify_strict(message, &signature)
+        .map_err(|_| "Invalid signature".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dev_address() {
+        let alice = dev_address("Alice");
+        assert_eq!(alice.len(), 40);
+        assert_eq!(alice, dev_address("Alice"));
+        assert_ne!(alice, dev_address("Bob"));
+    }
+
+    #[test]
+    fn test_validate_address() {
+        assert!(validate_address(&dev_address("Alice")).is_ok());
+        assert!(validate_address("").is_err());
+        assert!(validate_address("Alice").is_err());
+        assert!(validate_address(&dev_address("Alice").to_uppercase()).is_err());
+        assert!(validate_address(&format!("{}00", dev_address("Alice"))).is_err());
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let key = dev_key("Alice");
+        let public_key = hex::encode(key.verifying_key().as_bytes());
+        let signature = sign(&key, b"payload");
+        let result = verify(&dev_address("Alice"), &public_key, b"payload", &signature);
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_verify_wrong_sender() {
+        let key = dev_key("Alice");
+        let public_key = hex::encode(key.verifying_key().as_bytes());
+        let signature = sign(&key, b"payload");
+        let result = verify(&dev_address("Bob"), &public_key, b"payload", &signature);
+        assert_eq!(result.unwrap_err(), "Public key does not match sender");
+    }
+
+    #[test]
+    fn test_verify_tampered_message() {
+        let key = dev_key("Alice");
+        let public_key = hex::encode(key.verifying_key().as_bytes());
+        let signature = sign(&key, b"payload");
+        let result = verify(&dev_address("Alice"), &public_key, b"tampered", &signature);
+        assert_eq!(result.unwrap_err(), "Invalid signature");
+    }
+}
@@ -0,0 +1,139 @@
+use std::fmt;
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// Exact amount of tokens, in base units. On chain and in JSON amounts are always integer base
+/// units; `parse` and `format` convert from and to the chain spec's `decimals` for people.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize)]
+#[serde(transparent)]
+pub struct Amount(pub u64);
+
+impl Amount {
+    pub const ZERO: Amount = Amount(0);
+
+    pub fn is_zero(&self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn checked_add(self, other: Amount) -> Option<Amount> {
+        self.0.checked_add(other.0).map(Amount)
+    }
+
+    pub fn checked_sub(self, other: Amount) -> Option<Amount> {
+        self.0.checked_sub(other.0).map(Amount)
+    }
+
+    /// Parses a human readable amount such as "12.5" into base units with `decimals` places.
+    /// Negative values and more fractional digits than `decimals` are rejected.
+    pub fn parse(input: &str, decimals: u32) -> Result<Amount, String> {
+        let input = input.trim();
+        if input.starts_with('-') { return Err("Amount cannot be negative".to_string()); }
+
+        let (whole, fraction) = input.split_once('.').unwrap_or((input, ""));
+        if whole.is_empty() && fraction.is_empty() { return Err("Amount is empty".to_string()); }
+        if !whole.chars().chain(fraction.chars()).all(|c| c.is_ascii_digit()) {
+            return Err("Amount must be a decimal number".to_string());
+        }
+        if fraction.len() > decimals as usize {
+            return Err(format!("Amount has more than {} decimal places", decimals));
+        }
+
+        let overflow = || "Amount overflow".to_string();
+        let scale = 10u64.checked_pow(decimals).ok_or_else(overflow)?;
+        let whole: u64 = if whole.is_empty() { 0 } else { whole.parse().map_err(|_| overflow())? };
+        let fraction: u64 = if fraction.is_empty() { 0 } else {
+            let padded = format!("{:0<width$}", fraction, width = decimals as usize);
+            padded.parse().map_err(|_| overflow())?
+        };
+        whole.checked_mul(scale)
+            .and_then(|units| units.checked_add(fraction))
+            .map(Amount)
+            .ok_or_else(overflow)
+    }
+
+    pub fn format(&self, decimals: u32) -> String {
+        if decimals == 0 { return self.0.to_string(); }
+        let scale = 10u64.pow(decimals);
+        format!("{}.{:0width$}", self.0 / scale, self.0 % scale, width = decimals as usize)
+    }
+}
+
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl From<u64> for Amount {
+    fn from(value: u64) -> Self {
+        Amount(value)
+    }
+}
+
+struct AmountVisitor;
+
+impl Visitor<'_> for AmountVisitor {
+    type Value = Amount;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a non-negative integer amount in base units")
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<Amount, E> {
+        Ok(Amount(value))
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<Amount, E> {
+        u64::try_from(value).map(Amount).map_err(|_| E::custom("amount cannot be negative"))
+    }
+
+    fn visit_f64<E: de::Error>(self, _value: f64) -> Result<Amount, E> {
+        Err(E::custom("amount must be an integer number of base units"))
+    }
+}
+
+impl<'de> Deserialize<'de> for Amount {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Amount, D::Error> {
+        deserializer.deserialize_u64(AmountVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Amount::parse("12", 2), Ok(Amount(1200)));
+        assert_eq!(Amount::parse("12.5", 2), Ok(Amount(1250)));
+        assert_eq!(Amount::parse("0.05", 2), Ok(Amount(5)));
+        assert_eq!(Amount::parse(".5", 2), Ok(Amount(50)));
+        assert_eq!(Amount::parse("7", 0), Ok(Amount(7)));
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid() {
+        assert_eq!(Amount::parse("0.5", 0).unwrap_err(), "Amount has more than 0 decimal places");
+        assert_eq!(Amount::parse("1.005", 2).unwrap_err(), "Amount has more than 2 decimal places");
+        assert_eq!(Amount::parse("-1", 2).unwrap_err(), "Amount cannot be negative");
+        assert_eq!(Amount::parse("1e3", 2).unwrap_err(), "Amount must be a decimal number");
+        assert_eq!(Amount::parse("", 2).unwrap_err(), "Amount is empty");
+        assert_eq!(Amount::parse("184467440737095517", 2).unwrap_err(), "Amount overflow");
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(Amount(1250).format(2), "12.50");
+        assert_eq!(Amount(5).format(2), "0.05");
+        assert_eq!(Amount(5).format(0), "5");
+    }
+
+    #[test]
+    fn test_json() {
+        let amount: Amount = serde_json::from_str("50").unwrap();
+        assert_eq!(amount, Amount(50));
+        assert_eq!(serde_json::to_string(&amount).unwrap(), "50");
+        assert!(serde_json::from_str::<Amount>("0.5").is_err());
+        assert!(serde_json::from_str::<Amount>("-5").is_err());
+    }
+}
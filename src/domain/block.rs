@@ -1,6 +1,8 @@
 use digest::Digest;
 use serde::{Deserialize, Serialize};
 use sha2::Sha256;
+use crate::domain::encoding::encode_fields;
+use crate::domain::merkle::{self, MerkleProof};
 use crate::domain::transaction::Transaction;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -8,37 +10,185 @@ pub struct Block  {
     pub index: usize,
     pub timestamp: String,
     pub proof: u64,
+    /// Leading zero bits the proof of work must reach, see `pow::next_difficulty`.
+    pub difficulty: u32,
     pub previous_hash: String,
+    pub transactions_root: String,
+    /// Root of the state after executing this block, see `State::state_root`. Set by the block
+    /// builder once the block's transactions are known.
+    pub state_root: String,
     pub transactions: Vec<Transaction>
 }
 
+/// Everything in a block except the transaction bodies. Enough to recompute the block hash.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockHeader {
+    pub index: usize,
+    pub timestamp: String,
+    pub proof: u64,
+    pub difficulty: u32,
+    pub previous_hash: String,
+    pub transactions_root: String,
+    pub state_root: String,
+}
+
+/// Proof that a transaction is included in the block with the given header.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransactionProof {
+    pub transaction_hash: String,
+    pub block_hash: String,
+    pub header: BlockHeader,
+    pub proof: MerkleProof,
+}
+
+impl BlockHeader {
+    /// Raw SHA-256 of the length-prefixed header fields; proof of work is checked against these
+    /// bytes.
+    pub fn digest(&self) -> Vec<u8> {
+        let index = self.index.to_string();
+        let proof = self.proof.to_string();
+        let difficulty = self.difficulty.to_string();
+        let data = encode_fields(&[
+            index.as_bytes(),
+            self.timestamp.as_bytes(),
+            proof.as_bytes(),
+            difficulty.as_bytes(),
+            self.previous_hash.as_bytes(),
+            self.transactions_root.as_bytes(),
+            self.state_root.as_bytes(),
+        ]);
+        Sha256::digest(data).to_vec()
+    }
+
+    pub fn hash(&self) -> String {
+        hex::encode(self.digest())
+    }
+}
+
+impl TransactionProof {
+    /// Checks the header hashes to `block_hash` and the transaction is under its transactions root.
+    pub fn verify(&self) -> bool {
+        self.header.hash() == self.block_hash
+            && merkle::verify_proof(&self.header.transactions_root, &self.transaction_hash, &self.proof)
+    }
+}
+
 impl Block {
+    pub fn new(index: usize, timestamp: String, proof: u64, difficulty: u32, previous_hash: String, transactions: Vec<Transaction>) -> Self {
+        let transactions_root = Block::compute_transactions_root(&transactions);
+        Block {
+            index,
+            timestamp,
+            proof,
+            difficulty,
+            previous_hash,
+            transactions_root,
+            state_root: String::new(),
+            transactions,
+        }
+    }
+
+    pub fn header(&self) -> BlockHeader {
+        BlockHeader {
+            index: self.index,
+            timestamp: self.timestamp.clone(),
+            proof: self.proof,
+            difficulty: self.difficulty,
+            previous_hash: self.previous_hash.clone(),
+            transactions_root: self.transactions_root.clone(),
+            state_root: self.state_root.clone(),
+        }
+    }
+
     pub fn hash(&self) -> String {
-        let mut hasher = Sha256::new();
-        let data = format!("{}{}{}{}", self.index, self.timestamp, self.proof, self.previous_hash);
-        hasher.update(data);
-        let result = hasher.finalize();
-        format!("{:x}", result)
+        self.header().hash()
+    }
+
+    /// Address the block's coinbase pays, which also collects the transaction fees.
+    pub fn author(&self) -> Option<&str> {
+        self.transactions.first()
+            .and_then(|coinbase| coinbase.coinbase_payout())
+            .map(|(beneficiary, _)| beneficiary)
+            .filter(|beneficiary| !beneficiary.is_empty())
+    }
+
+    /// Merkle root of the transaction hashes, recomputed from the transaction contents
+    /// so a tampered transaction changes the root even if its `hash` field was left alone.
+    pub fn compute_transactions_root(transactions: &[Transaction]) -> String {
+        let hashes: Vec<String> = transactions.iter().map(|transaction| transaction.compute_hash()).collect();
+        merkle::merkle_root(&hashes)
+    }
+
+    pub fn has_valid_transactions_root(&self) -> bool {
+        self.transactions_root == Block::compute_transactions_root(&self.transactions)
+    }
+
+    pub fn transaction_proof(&self, transaction_hash: &str) -> Option<TransactionProof> {
+        let hashes: Vec<String> = self.transactions.iter().map(|transaction| transaction.compute_hash()).collect();
+        let index = hashes.iter().position(|hash| hash == transaction_hash)?;
+        let proof = merkle::merkle_proof(&hashes, index)?;
+        Some(TransactionProof {
+            transaction_hash: transaction_hash.to_string(),
+            block_hash: self.hash(),
+            header: self.header(),
+            proof,
+        })
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::domain::transaction::tests::dev_transfer;
+    use crate::domain::amount::Amount;
+
     #[test]
     fn test_block_hash() {
-        let block = Block {
-            index: 0,
-            timestamp: "2021-08-01T00:00:00".to_string(),
-            proof: 0,
-            previous_hash: "0".to_string(),
-            transactions: vec![],
-        };
+        let block = Block::new(0, "2021-08-01T00:00:00".to_string(), 0, 16, "0".to_string(), vec![]);
         let hash = block.hash();
         assert_eq!(hash.len(), 64);
-        assert_eq!(hash.is_empty(), false);
+        assert!(!hash.is_empty());
     }
 
-}
+    #[test]
+    fn test_header_fields_do_not_run_together() {
+        let mut block = Block::new(1, "2021-08-01T00:00:00".to_string(), 11, 6, "0".to_string(), vec![]);
+        let hash = block.hash();
+        block.proof = 1;
+        block.difficulty = 16;
+        assert_ne!(block.hash(), hash);
+    }
 
+    #[test]
+    fn test_block_hash_covers_transactions() {
+        let transaction = dev_transfer("Alice", 0, "Bob", 10, 0);
+        let mut block = Block::new(1, "2021-08-01T00:00:00".to_string(), 0, 16, "0".to_string(), vec![transaction]);
+        let hash = block.hash();
+        assert!(block.has_valid_transactions_root());
+
+        block.transactions[0].fee = Amount(100);
+        assert!(!block.has_valid_transactions_root());
+
+        block.transactions_root = Block::compute_transactions_root(&block.transactions);
+        assert_ne!(block.hash(), hash);
+    }
+
+    #[test]
+    fn test_transaction_proof() {
+        let transactions: Vec<Transaction> = (0..3)
+            .map(|nonce| dev_transfer("Alice", nonce, "Bob", 10, 0))
+            .collect();
+        let block = Block::new(1, "2021-08-01T00:00:00".to_string(), 0, 16, "0".to_string(), transactions.clone());
+
+        let proof = block.transaction_proof(&transactions[1].hash).unwrap();
+        assert_eq!(proof.block_hash, block.hash());
+        assert!(proof.verify());
+
+        let mut forged = proof.clone();
+        forged.transaction_hash = transactions[2].hash.clone();
+        assert!(!forged.verify());
 
+        assert!(block.transaction_proof("unknown").is_none());
+    }
+
+}
@@ -0,0 +1,55 @@
+use serde::{Deserialize, Serialize};
+use crate::domain::amount::Amount;
+use crate::domain::encoding::encode_fields;
+
+/// Every call the runtime can dispatch, one variant per pallet. In JSON a call reads as
+/// `{"Balances": {"Transfer": {"to": "<address>", "amount": 10}}}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum RuntimeCall {
+    Balances(BalancesCall),
+    System(SystemCall),
+}
+
+/// Calls dispatched by the balances pallet.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum BalancesCall {
+    Transfer { to: String, amount: Amount },
+}
+
+/// Calls dispatched by the system pallet.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SystemCall {
+    /// Records `message` on chain and does nothing else.
+    Remark { message: String },
+}
+
+impl RuntimeCall {
+    /// Name of the pallet the call is dispatched to.
+    pub fn pallet(&self) -> &'static str {
+        match self {
+            RuntimeCall::Balances(_) => "Balances",
+            RuntimeCall::System(_) => "System",
+        }
+    }
+
+    /// Canonical bytes of the call, signed as part of the transaction payload: the pallet, the
+    /// call name and its arguments, each length-prefixed.
+    pub fn encode(&self) -> Vec<u8> {
+        match self {
+            RuntimeCall::Balances(BalancesCall::Transfer { to, amount }) => {
+                encode_fields(&[b"Balances", b"Transfer", to.as_bytes(), amount.to_string().as_bytes()])
+            }
+            RuntimeCall::System(SystemCall::Remark { message }) => {
+                encode_fields(&[b"System", b"Remark", message.as_bytes()])
+            }
+        }
+    }
+
+    /// Amount the call moves out of the origin's balance, on top of the transaction fee.
+    pub fn value(&self) -> Amount {
+        match self {
+            RuntimeCall::Balances(BalancesCall::Transfer { amount, .. }) => *amount,
+            RuntimeCall::System(_) => Amount::ZERO,
+        }
+    }
+}
@@ -0,0 +1,21 @@
+/// Concatenates `fields`, each prefixed with its length, so that no two field lists share an
+/// encoding.
+pub fn encode_fields(fields: &[&[u8]]) -> Vec<u8> {
+    let mut encoded = Vec::new();
+    for field in fields {
+        encoded.extend_from_slice(&(field.len() as u64).to_be_bytes());
+        encoded.extend_from_slice(field);
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_field_boundaries_are_encoded() {
+        assert_ne!(encode_fields(&[b"11", b"6"]), encode_fields(&[b"1", b"16"]));
+        assert_ne!(encode_fields(&[b"ab"]), encode_fields(&[b"a", b"b"]));
+    }
+}
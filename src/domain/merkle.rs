@@ -0,0 +1,170 @@
+use digest::Digest;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+/// Root committed by a block without transactions.
+pub const EMPTY_ROOT: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+// Leaves and inner nodes are hashed with different prefixes so a leaf can never be passed off as a node.
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+
+pub fn hash_leaf(leaf: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update([LEAF_PREFIX]);
+    hasher.update(leaf.as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+pub fn hash_node(left: &str, right: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update([NODE_PREFIX]);
+    hasher.update(left.as_bytes());
+    hasher.update(right.as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+/// Merkle root over a list of leaves (transaction hashes). An odd node at the end of a level is
+/// promoted unchanged to the next level instead of being paired with itself.
+pub fn merkle_root(leaves: &[String]) -> String {
+    if leaves.is_empty() {
+        return EMPTY_ROOT.to_string();
+    }
+
+    let mut level: Vec<String> = leaves.iter().map(|leaf| hash_leaf(leaf)).collect();
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| match pair {
+                [left, right] => hash_node(left, right),
+                [single] => single.clone(),
+                _ => unreachable!(),
+            })
+            .collect();
+    }
+    level.remove(0)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    Left,
+    Right,
+}
+
+/// One level of an inclusion proof: the sibling hash and which side it sits on.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProofStep {
+    pub hash: String,
+    pub side: Side,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MerkleProof {
+    pub leaf_index: usize,
+    pub steps: Vec<ProofStep>,
+}
+
+/// Builds the proof that `leaves[index]` is part of `merkle_root(leaves)`.
+pub fn merkle_proof(leaves: &[String], index: usize) -> Option<MerkleProof> {
+    if index >= leaves.len() {
+        return None;
+    }
+
+    let mut steps = vec![];
+    let mut position = index;
+    let mut level: Vec<String> = leaves.iter().map(|leaf| hash_leaf(leaf)).collect();
+    while level.len() > 1 {
+        let sibling = position ^ 1;
+        if sibling < level.len() {
+            let side = if sibling < position { Side::Left } else { Side::Right };
+            steps.push(ProofStep { hash: level[sibling].clone(), side });
+        }
+        level = level
+            .chunks(2)
+            .map(|pair| match pair {
+                [left, right] => hash_node(left, right),
+                [single] => single.clone(),
+                _ => unreachable!(),
+            })
+            .collect();
+        position /= 2;
+    }
+    Some(MerkleProof { leaf_index: index, steps })
+}
+
+pub fn verify_proof(root: &str, leaf: &str, proof: &MerkleProof) -> bool {
+    let computed = proof.steps.iter().fold(hash_leaf(leaf), |current, step| match step.side {
+        Side::Left => hash_node(&step.hash, &current),
+        Side::Right => hash_node(&current, &step.hash),
+    });
+    computed == root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaves(count: usize) -> Vec<String> {
+        (0..count).map(|i| format!("tx{}", i)).collect()
+    }
+
+    #[test]
+    fn test_empty_root() {
+        assert_eq!(merkle_root(&[]), EMPTY_ROOT);
+    }
+
+    #[test]
+    fn test_single_leaf() {
+        let leaves = leaves(1);
+        assert_eq!(merkle_root(&leaves), hash_leaf(&leaves[0]));
+    }
+
+    #[test]
+    fn test_odd_leaves() {
+        let leaves = leaves(3);
+        let left = hash_node(&hash_leaf(&leaves[0]), &hash_leaf(&leaves[1]));
+        assert_eq!(merkle_root(&leaves), hash_node(&left, &hash_leaf(&leaves[2])));
+    }
+
+    #[test]
+    fn test_root_depends_on_order_and_content() {
+        let mut leaves = leaves(4);
+        let root = merkle_root(&leaves);
+        leaves.swap(0, 1);
+        assert_ne!(merkle_root(&leaves), root);
+        leaves.swap(0, 1);
+        leaves[3] = "tampered".to_string();
+        assert_ne!(merkle_root(&leaves), root);
+    }
+
+    #[test]
+    fn test_proof_every_leaf() {
+        for count in 1..=9 {
+            let leaves = leaves(count);
+            let root = merkle_root(&leaves);
+            for (index, leaf) in leaves.iter().enumerate() {
+                let proof = merkle_proof(&leaves, index).unwrap();
+                assert!(verify_proof(&root, leaf, &proof), "leaf {} of {}", index, count);
+            }
+        }
+    }
+
+    #[test]
+    fn test_proof_rejects_wrong_leaf() {
+        let leaves = leaves(5);
+        let root = merkle_root(&leaves);
+        let proof = merkle_proof(&leaves, 2).unwrap();
+        assert!(!verify_proof(&root, &leaves[3], &proof));
+        assert!(!verify_proof(EMPTY_ROOT, &leaves[2], &proof));
+        assert!(merkle_proof(&leaves, 5).is_none());
+    }
+
+    #[test]
+    fn test_duplicated_last_leaf_changes_root() {
+        let three = leaves(3);
+        let mut four = three.clone();
+        four.push(three[2].clone());
+        assert_ne!(merkle_root(&three), merkle_root(&four));
+    }
+}
@@ -1,2 +1,7 @@
 pub mod block;
-pub(crate) mod transaction;
\ No newline at end of file
+pub(crate) mod transaction;
+pub(crate) mod account;
+pub(crate) mod amount;
+pub(crate) mod merkle;
+pub(crate) mod encoding;
+pub(crate) mod call;
@@ -1,71 +1,238 @@
 use chrono::Utc;
 use digest::Digest;
+use ed25519_dalek::SigningKey;
 use rocket::serde::Serialize;
 use serde::Deserialize;
 use sha2::Sha256;
+use crate::domain::account;
+use crate::domain::amount::Amount;
+use crate::domain::call::{BalancesCall, RuntimeCall};
+use crate::domain::encoding::encode_fields;
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+/// Sender of coinbase transactions. It is not a valid address, so no signed transaction can use it.
+pub const COINBASE_SENDER: &str = "coinbase";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Transaction {
     pub hash: String,
     pub timestamp: Option<String>,
     pub sender: String,
-    pub to: String,
-    pub amount: f64,
-    pub message: String,
+    /// Chain the transaction is meant for, so it cannot be replayed on another chain.
+    pub chain_id: String,
+    pub nonce: u64,
+    /// What the transaction does, dispatched to the pallet it names.
+    pub call: RuntimeCall,
+    /// Paid by the sender to the author of the block that includes the transaction.
+    #[serde(default)]
+    pub fee: Amount,
+    pub public_key: String,
+    pub signature: String,
 }
 
 
 impl Transaction {
-    pub fn new(sender: String, to: String, amount: f64, message: String) -> Self {
-        let timestamp = Some(Utc::now().to_rfc3339());
-        let hash = Transaction::hash(&sender, &to, amount, &message, timestamp.clone());
-        Transaction {
-            hash,
-            timestamp,
-            sender,
-            to,
-            amount,
-            message,
+    pub fn new(key: &SigningKey, chain_id: &str, nonce: u64, call: RuntimeCall, fee: Amount) -> Self {
+        let public_key = key.verifying_key();
+        let mut transaction = Transaction {
+            hash: String::new(),
+            timestamp: Some(Utc::now().to_rfc3339()),
+            sender: account::address(&public_key),
+            chain_id: chain_id.to_string(),
+            nonce,
+            call,
+            fee,
+            public_key: hex::encode(public_key.as_bytes()),
+            signature: String::new(),
+        };
+        transaction.sign(key);
+        transaction
+    }
+
+    /// Signed `Balances::Transfer` of `amount` to `to`.
+    pub fn transfer(key: &SigningKey, chain_id: &str, nonce: u64, to: String, amount: Amount, fee: Amount) -> Self {
+        Transaction::new(key, chain_id, nonce, RuntimeCall::Balances(BalancesCall::Transfer { to, amount }), fee)
+    }
+
+    /// Unsigned transaction that pays the block reward to `beneficiary`. The nonce is the block
+    /// height so that coinbase transactions never share a hash. Its transfer is minted rather than
+    /// taken from the sender.
+    pub fn coinbase(height: u64, timestamp: String, beneficiary: String, amount: Amount) -> Self {
+        let mut transaction = Transaction {
+            hash: String::new(),
+            timestamp: Some(timestamp),
+            sender: COINBASE_SENDER.to_string(),
+            chain_id: String::new(),
+            nonce: height,
+            call: RuntimeCall::Balances(BalancesCall::Transfer { to: beneficiary, amount }),
+            fee: Amount::ZERO,
+            public_key: String::new(),
+            signature: String::new(),
+        };
+        transaction.hash = transaction.compute_hash();
+        transaction
+    }
+
+    pub fn is_coinbase(&self) -> bool {
+        self.sender == COINBASE_SENDER
+    }
+
+    /// Beneficiary and amount a coinbase pays, `None` for other transactions.
+    pub fn coinbase_payout(&self) -> Option<(&str, Amount)> {
+        match &self.call {
+            RuntimeCall::Balances(BalancesCall::Transfer { to, amount }) if self.is_coinbase() => Some((to, *amount)),
+            _ => None,
         }
     }
 
-    pub fn hash(sender: &str, to: &str, amount: f64, message: &str, timestamp: Option<String>) -> String {
+    /// Canonical bytes covered by both the hash and the signature.
+    /// Fields are length-prefixed so that no two transactions share a payload.
+    pub fn payload(&self) -> Vec<u8> {
+        let timestamp = self.timestamp.clone().unwrap_or_default();
+        let nonce = self.nonce.to_string();
+        let fee = self.fee.to_string();
+        let call = self.call.encode();
+        encode_fields(&[self.chain_id.as_bytes(), self.sender.as_bytes(), nonce.as_bytes(), &call, fee.as_bytes(), timestamp.as_bytes(), self.public_key.as_bytes()])
+    }
+
+    pub fn compute_hash(&self) -> String {
         let mut hasher = Sha256::new();
-        let data = format!("{}{}{}{}{}", sender, to, amount, message, timestamp.unwrap());
-        hasher.update(data);
+        hasher.update(self.payload());
         let result = hasher.finalize();
         format!("{:x}", result)
+    }
+
+    pub fn sign(&mut self, key: &SigningKey) {
+        self.hash = self.compute_hash();
+        self.signature = account::sign(key, &self.payload());
+    }
+
+    /// Size of the transaction as it is stored in a block, used for block size limits.
+    pub fn encoded_len(&self) -> usize {
+        serde_json::to_vec(self).map(|bytes| bytes.len()).unwrap_or(0)
+    }
 
+    /// Checks the hash matches the payload and the signature belongs to the sender account.
+    pub fn verify(&self) -> Result<(), String> {
+        if self.timestamp.is_none() { return Err("Missing timestamp".to_string()); }
+        if self.hash != self.compute_hash() { return Err("Invalid transaction hash".to_string()); }
+        account::verify(&self.sender, &self.public_key, &self.payload(), &self.signature)
     }
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
 
+    /// Signed transfer between development accounts, see `account::dev_key`.
+    pub(crate) fn dev_transfer(from: &str, nonce: u64, to: &str, amount: u64, fee: u64) -> Transaction {
+        Transaction::transfer(&account::dev_key(from), account::DEV_CHAIN_ID, nonce, account::dev_address(to), Amount(amount), Amount(fee))
+    }
+
+    use crate::domain::call::SystemCall;
+
+    fn remark(message: &str) -> RuntimeCall {
+        RuntimeCall::System(SystemCall::Remark { message: message.to_string() })
+    }
+
     #[test]
     fn test_transaction_hash() {
-        let sender = "Alice";
-        let to = "Bob";
-        let amount = 10.0;
-        let message = "Lohann - Dev Master";
-        let timestamp = Some(Utc::now().to_rfc3339());
-        let hash = Transaction::hash(sender, to, amount, message, timestamp);
+        let transaction = Transaction::new(&account::dev_key("Alice"), account::DEV_CHAIN_ID, 0, remark("Lohann - Dev Master"), Amount::ZERO);
+        let hash = transaction.compute_hash();
         assert_eq!(hash.len(), 64);
-        assert_eq!(hash.is_empty(), false);
+        assert!(!hash.is_empty());
+        assert_eq!(hash, transaction.hash);
     }
 
     #[test]
     fn test_transaction_new() {
-        let sender = "Alice".to_string();
-        let to = "Bob".to_string();
-        let amount = 10.0;
-        let message = "Lohann - Dev Master".to_string();
-        let transaction = Transaction::new(sender.clone(), to.clone(), amount, message.clone());
-        assert_eq!(transaction.sender, sender);
-        assert_eq!(transaction.to, to);
-        assert_eq!(transaction.amount, amount);
-        assert_eq!(transaction.message, message);
+        let key = account::dev_key("Alice");
+        let transaction = Transaction::new(&key, account::DEV_CHAIN_ID, 0, remark("Lohann - Dev Master"), Amount::ZERO);
+        assert_eq!(transaction.sender, account::dev_address("Alice"));
+        assert_eq!(transaction.call, remark("Lohann - Dev Master"));
+        assert!(transaction.verify().is_ok());
+
+        let transfer = Transaction::transfer(&key, account::DEV_CHAIN_ID, 0, "Bob".to_string(), Amount(10), Amount::ZERO);
+        assert_eq!(transfer.call, RuntimeCall::Balances(BalancesCall::Transfer { to: "Bob".to_string(), amount: Amount(10) }));
+        assert_eq!(transfer.call.value(), Amount(10));
+        assert_ne!(transfer.hash, transaction.hash);
+    }
+
+    #[test]
+    fn test_call_is_signed() {
+        let mut transaction = Transaction::new(&account::dev_key("Alice"), account::DEV_CHAIN_ID, 0, remark("hello"), Amount::ZERO);
+        transaction.call = remark("hello!");
+        assert_eq!(transaction.verify().unwrap_err(), "Invalid transaction hash");
     }
-}
 
+    #[test]
+    fn test_call_json() {
+        let transaction = Transaction::transfer(&account::dev_key("Alice"), account::DEV_CHAIN_ID, 0, "Bob".to_string(), Amount(10), Amount::ZERO);
+        let json = serde_json::to_value(&transaction).unwrap();
+        assert_eq!(json["call"], serde_json::json!({"Balances": {"Transfer": {"to": "Bob", "amount": 10}}}));
+        let decoded: Transaction = serde_json::from_value(json).unwrap();
+        assert!(decoded.verify().is_ok());
+    }
+
+    #[test]
+    fn test_transaction_verify_tampered() {
+        let mut transaction = Transaction::transfer(&account::dev_key("Alice"), account::DEV_CHAIN_ID, 0, "Bob".to_string(), Amount(10), Amount::ZERO);
+        transaction.call = RuntimeCall::Balances(BalancesCall::Transfer { to: "Bob".to_string(), amount: Amount(100) });
+        assert_eq!(transaction.verify().unwrap_err(), "Invalid transaction hash");
+
+        transaction.hash = transaction.compute_hash();
+        assert_eq!(transaction.verify().unwrap_err(), "Invalid signature");
+    }
+
+    #[test]
+    fn test_transaction_fee_is_signed() {
+        let mut transaction = Transaction::transfer(&account::dev_key("Alice"), account::DEV_CHAIN_ID, 0, "Bob".to_string(), Amount(10), Amount(2));
+        assert!(transaction.verify().is_ok());
+        transaction.fee = Amount::ZERO;
+        assert_eq!(transaction.verify().unwrap_err(), "Invalid transaction hash");
+    }
+
+    #[test]
+    fn test_chain_id_is_signed() {
+        let mut transaction = dev_transfer("Alice", 0, "Bob", 10, 0);
+        transaction.chain_id = "abdala-test".to_string();
+        assert_eq!(transaction.verify().unwrap_err(), "Invalid transaction hash");
+    }
+
+    #[test]
+    fn test_transaction_nonce_in_hash() {
+        let key = account::dev_key("Alice");
+        let mut transaction = Transaction::transfer(&key, account::DEV_CHAIN_ID, 0, "Bob".to_string(), Amount(10), Amount::ZERO);
+        let hash = transaction.hash.clone();
+        transaction.nonce = 1;
+        assert_ne!(transaction.compute_hash(), hash);
+        assert_eq!(transaction.verify().unwrap_err(), "Invalid transaction hash");
+    }
+
+    #[test]
+    fn test_transaction_verify_impersonation() {
+        let mut transaction = Transaction::transfer(&account::dev_key("Mallory"), account::DEV_CHAIN_ID, 0, "Mallory".to_string(), Amount(10), Amount::ZERO);
+        transaction.sender = account::dev_address("Alice");
+        transaction.sign(&account::dev_key("Mallory"));
+        assert_eq!(transaction.verify().unwrap_err(), "Public key does not match sender");
+    }
+
+    #[test]
+    fn test_coinbase_cannot_be_relayed() {
+        let coinbase = Transaction::coinbase(1, Utc::now().to_rfc3339(), account::dev_address("Alice"), Amount(50));
+        assert!(coinbase.is_coinbase());
+        assert_eq!(coinbase.hash, coinbase.compute_hash());
+        assert!(coinbase.verify().is_err());
+    }
+
+    #[test]
+    fn test_transaction_rejects_fractional_amount() {
+        let transaction = Transaction::transfer(&account::dev_key("Alice"), account::DEV_CHAIN_ID, 0, "Bob".to_string(), Amount(10), Amount::ZERO);
+        let mut json = serde_json::to_value(&transaction).unwrap();
+        assert_eq!(json["call"]["Balances"]["Transfer"]["amount"], 10);
+
+        json["call"]["Balances"]["Transfer"]["amount"] = serde_json::json!(0.5);
+        assert!(serde_json::from_value::<Transaction>(json.clone()).is_err());
+        json["call"]["Balances"]["Transfer"]["amount"] = serde_json::json!(-10);
+        assert!(serde_json::from_value::<Transaction>(json).is_err());
+    }
+}
@@ -11,31 +11,35 @@ async fn main() {
     //run_chain();
 }
 
+#[allow(dead_code)]
 fn run_chain()  {
     println!("ABDALA CHAIN - A simple blockchain implementation in Rust");
 
     let  mut chain = Blockchain::new();
-    let previous_block = chain.get_previous_block().clone();
+    let mut block = chain.create_block();
 
-    let mut proof = chain.proof_of_work(previous_block.proof);
-    let mut block = chain.create_block(proof, previous_block.previous_hash.clone());
-
-    while true {
+    loop {
         println!("Block {} has been added to the chain", block.index);
         println!("Hash: {}", block.hash());
         println!("Proof: {}", block.proof);
         println!("Previous Hash: {}", block.previous_hash);
         println!("Timestamp: {}", block.timestamp);
         println!("\n");
-        proof = chain.proof_of_work(block.proof);
-        block = chain.create_block(proof, block.hash());
+        block = chain.create_block();
 
     }
 
 }
 
+/// Value following `name` on the command line, e.g. `--data-dir <path>`.
+fn arg_value(name: &str) -> Option<String> {
+    let args: Vec<String> = std::env::args().collect();
+    let position = args.iter().position(|arg| arg == name)?;
+    args.get(position + 1).cloned()
+}
+
 async fn run_rpc() {
-    rpc::start_server().await.unwrap();
+    rpc::start_server(arg_value("--data-dir"), arg_value("--chain-spec")).await.unwrap();
     println!("RPC server started at http://localhost:8087");
 
 }
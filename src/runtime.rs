@@ -1,185 +1,293 @@
-use std::collections::BTreeMap;
-use std::sync::Mutex;
-use chrono::Utc;
-use digest::Digest;
-use serde::{Serialize, Deserialize};
-use crate::core_client::balance::Pallet;
-use crate::domain::block::Block;
-
-use crate::core_client::system::Pallet as SystemPallet;
-use reqwest;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use chrono::{DateTime, Duration, Utc};
+use crate::domain::block::{Block, TransactionProof};
+
+use crate::core_client::chain_spec::ChainSpec;
+use crate::core_client::authoring::{self, AuthoringPolicy};
+use crate::core_client::executive::{genesis_block, Receipt, State};
+use crate::core_client::fork_choice::{self, Reorg};
+use crate::core_client::mempool::{Mempool, MempoolConfig};
+use crate::core_client::pallet::BlockContext;
+use crate::core_client::pow;
+use crate::core_client::storage::{Storage, StoredState};
 use reqwest::Client;
 use serde_json::Value;
+use tokio::sync::{watch, Notify};
 use crate::domain::transaction::Transaction;
+use crate::domain::amount::Amount;
 
 #[derive(Debug)]
 pub struct Blockchain {
     pub chain: Mutex<Vec<Block>>,
-    transaction_pool: Vec<Transaction>,
-    balances: Pallet,
-    system: SystemPallet,
+    transaction_pool: Mempool,
+    state: State,
+    /// Receipts of every transaction on our chain, by transaction hash.
+    receipts: BTreeMap<String, Receipt>,
+    spec: ChainSpec,
     nodes: Vec<String>,
-
+    storage: Option<Storage>,
+    cross_check_peer_state: bool,
+    authoring: AuthoringPolicy,
+    /// Hash of our tip; the miner watches it to abandon blocks built on an outdated tip.
+    tip: watch::Sender<String>,
+    /// Wakes the miner when there may be something new to mine.
+    miner_wakeup: Arc<Notify>,
 }
 
 impl Blockchain {
+    /// In-memory node on the development chain spec.
     pub fn new() -> Self {
-        let mut chain = Vec::new();
-        let genesis_block = Block {
-            index: 0,
-            timestamp: Utc::now().to_rfc3339(),
-            proof: 0,
-            previous_hash: "0".to_string(),
-            transactions: vec![],
-        };
-
-        let mut balances = Pallet::new();
-        balances.set_balance("Alice", 100).unwrap();
+        Blockchain::from_spec(ChainSpec::development())
+    }
 
-        chain.push(genesis_block);
+    pub fn from_spec(spec: ChainSpec) -> Self {
+        let genesis = genesis_block(&spec);
+        let (tip, _) = watch::channel(genesis.hash());
+        // Block 0 runs the pallet hooks like any other, so start from the state after it.
+        let (state, _) = State::from_chain(&spec, std::slice::from_ref(&genesis)).expect("genesis block has no transactions");
         Blockchain {
-            chain: Mutex::new(chain),
-            transaction_pool: vec![],
-            balances,
-            system: SystemPallet { nonce: Default::default() },
+            chain: Mutex::new(vec![genesis]),
+            transaction_pool: Mempool::new(MempoolConfig::default()),
+            state,
+            receipts: BTreeMap::new(),
+            spec,
             nodes: vec![],
+            storage: None,
+            cross_check_peer_state: false,
+            authoring: AuthoringPolicy::default(),
+            tip,
+            miner_wakeup: Arc::new(Notify::new()),
         }
     }
 
-    fn execute_transaction(&mut self, transaction: Transaction) -> Result<bool, String> {
-        let sender = transaction.sender.clone();
-        let receiver = transaction.to.clone();
-        let amount = transaction.amount;
+    /// Reopens the chain stored in `data_dir`, or initialises it with the genesis of `spec`.
+    pub fn open(data_dir: impl AsRef<Path>, spec: ChainSpec) -> Result<Self, String> {
+        let storage = Storage::open(data_dir)?;
+        let mut blockchain = Blockchain::from_spec(spec);
+        let blocks = storage.load_blocks()?;
+
+        if blocks.is_empty() {
+            storage.replace_blocks(&blockchain.get_chain())?;
+        } else {
+            // The block log is authoritative. The snapshot and receipt log save executing the
+            // blocks up to the snapshot, but may lag behind the block log after a crash between
+            // appending a block and saving what it produced.
+            let logged_receipts = storage.load_receipts().unwrap_or_default();
+            let restored = storage.load_state().ok().flatten()
+                .ok_or("No state snapshot".to_string())
+                .and_then(|snapshot| restore_chain(&blocks, &blockchain.spec, snapshot, &logged_receipts));
+            let (state, receipts) = match restored {
+                Ok(restored) => restored,
+                Err(e) => {
+                    println!("Could not restore the state snapshot ({}), rebuilding it from the blocks", e);
+                    validate_chain(&blocks, &blockchain.spec)?
+                }
+            };
+            blockchain.state = state;
+            blockchain.set_receipts(receipts);
+            *blockchain.chain.lock().unwrap() = blocks;
+            blockchain.update_tip();
+        }
+        let receipts = blockchain.receipts_by_block();
+        if storage.load_receipts().ok() != Some(receipts.clone()) {
+            storage.replace_receipts(&receipts)?;
+        }
+        storage.save_state(&blockchain.stored_state())?;
 
-        let inc_nonce_result = self.system.increment_nonce(&sender);
-        if inc_nonce_result.is_err() { return Err(inc_nonce_result.err().unwrap()); }
+        blockchain.storage = Some(storage);
+        Ok(blockchain)
+    }
 
-        if amount <= 0f64 {
-            return Err("Amount must be greater than 0".to_string());
-        }
+    fn set_receipts(&mut self, receipts: Vec<Receipt>) {
+        self.receipts = receipts.into_iter().map(|receipt| (receipt.transaction_hash.clone(), receipt)).collect();
+    }
 
-        if self.balances.balance(&sender) < amount as u64 {
-            return Err("Insufficient balance".to_string());
-        }
+    /// Receipt of the transaction `hash` if it is included in our chain.
+    pub fn transaction_receipt(&self, hash: &str) -> Option<Receipt> {
+        self.receipts.get(hash).cloned()
+    }
 
-        let transfer_result = self.balances.transfer(&sender, &receiver, amount as u64);
-        if transfer_result.is_err() {
-            return Err(transfer_result.err().unwrap());
+    /// Receipts of every block in our chain, in block and transaction order.
+    fn receipts_by_block(&self) -> Vec<Vec<Receipt>> {
+        self.chain.lock().unwrap().iter()
+            .map(|block| block.transactions.iter()
+                .filter_map(|transaction| self.receipts.get(&transaction.hash))
+                .filter(|receipt| receipt.block_index == block.index)
+                .cloned()
+                .collect())
+            .collect()
+    }
+
+    fn stored_state(&self) -> StoredState {
+        let chain = self.chain.lock().unwrap();
+        StoredState {
+            block_count: chain.len(),
+            block_hash: chain.last().unwrap().hash(),
+            storage: self.state.storage(),
         }
-        Ok(true)
     }
 
-    fn execute_transactions(&mut self, transactions: Vec<Transaction>) -> Result<bool, String> {
-        if transactions.is_empty() {
-            return Err("No transactions to execute".to_string());
+    /// Appends a newly created block and its receipts to the logs and then saves the state it
+    /// produced.
+    fn persist_block(&self, block: &Block, receipts: &[Receipt]) -> Result<(), String> {
+        if let Some(storage) = &self.storage {
+            storage.append_block(block)?;
+            storage.append_receipts(receipts)?;
+            storage.save_state(&self.stored_state())?;
         }
-        for transaction in transactions {
-            if let Err(e) = self.execute_transaction(transaction.clone()) {
-                println!("Error executing transaction: {:?}", e);
-                continue;
-            }
+        Ok(())
+    }
+
+    /// Rewrites the logs and state after the chain was replaced wholesale.
+    fn persist_chain(&self) -> Result<(), String> {
+        if let Some(storage) = &self.storage {
+            storage.replace_blocks(&self.get_chain())?;
+            storage.replace_receipts(&self.receipts_by_block())?;
+            storage.save_state(&self.stored_state())?;
         }
-        Ok(true)
+        Ok(())
     }
 
-    pub async fn add_transaction(&mut self, transaction: Transaction) {
-        let mut transaction = transaction.clone();
+    pub async fn add_transaction(&mut self, transaction: Transaction) -> Result<(), String> {
+        transaction.verify()?;
         self.consensus().await;
-        if transaction.hash.is_empty() {
-            transaction = Transaction {
-                hash: Transaction::hash(&transaction.sender, &transaction.to, transaction.amount, &transaction.message.clone(), transaction.timestamp),
-                timestamp: Some(Utc::now().to_rfc3339()),
-                sender: transaction.sender,
-                to: transaction.to,
-                amount: transaction.amount,
-                message: transaction.message,
-            } ; }
-
-       match (self.transaction_pool.len()) {
-           4 => {
-                let previous_block = self.get_previous_block();
-                let proof = self.proof_of_work(previous_block.proof);
-                let previous_hash = previous_block.hash();
-
-                self.transaction_pool.push(transaction);
-                let block = self.create_block(proof, previous_hash);
-           }
-              _ => {
-                self.transaction_pool.push(transaction);
-                }
-        }
+        self.submit_transaction(transaction)
     }
 
-    pub fn create_block(&mut self, proof: u64, previous_hash: String) -> Block {
-        let transactions = self.transaction_pool.clone();
-        let execute = self.execute_transactions(transactions.clone());
+    /// Admits `transaction` into the pool against our current state, see `Mempool::add`.
+    pub fn submit_transaction(&mut self, transaction: Transaction) -> Result<(), String> {
+        let now = Utc::now();
+        self.transaction_pool.prune(&self.state, now);
+        self.transaction_pool.add(transaction, &self.state, now)?;
+        self.miner_wakeup.notify_one();
+        Ok(())
+    }
 
-        let mut chain = self.chain.lock().unwrap();
+    /// Time left until the authoring policy wants the next block, `None` if nothing is due.
+    pub fn time_until_block(&self) -> Option<Duration> {
+        let tip_time = parse_block_time(&self.get_previous_block()).unwrap_or_else(|_| Utc::now());
+        self.authoring.time_until_block(self.ready_transactions().len(), self.spec.max_block_transactions, tip_time, Utc::now())
+    }
 
-        let block = Block {
-            index: chain.len() + 1,
-            timestamp: Utc::now().to_rfc3339(),
-            proof,
-            previous_hash,
-            transactions,
-        } ;
-        chain.push(block.clone());
-        self.transaction_pool = vec![];
-        block
+    /// Whether the miner should build a block now.
+    pub fn should_mine(&self) -> bool {
+        self.time_until_block() == Some(Duration::zero())
     }
 
-    pub fn get_previous_block(&self) -> Block {
-        let chain = self.chain.lock().unwrap();
-        chain.last().unwrap().clone()
+    pub fn set_authoring_policy(&mut self, policy: AuthoringPolicy) {
+        self.authoring = policy;
     }
 
-    pub fn proof_of_work(&self, previous_proof: u64) -> u64 {
-        let mut new_proof: u64 = previous_proof + 1; // Começa a partir de previous_proof + 1
-        let mut check_proof = false;
+    pub fn set_mempool_config(&mut self, config: MempoolConfig) {
+        self.transaction_pool.set_config(config);
+    }
 
-        while !check_proof {
-            let calc = new_proof.pow(2) - previous_proof.pow(2);
-            let mut hasher = sha2::Sha256::new();
-            hasher.update(calc.to_string().as_bytes());
-            let hash_result = format!("{:x}", hasher.finalize());
+    pub fn subscribe_tip(&self) -> watch::Receiver<String> {
+        self.tip.subscribe()
+    }
 
-            match &hash_result[..4] {
-                "0000" => check_proof = true,
-                _ => new_proof += 1,
-            }
-        }
-        new_proof
+    pub fn miner_wakeup(&self) -> Arc<Notify> {
+        self.miner_wakeup.clone()
     }
 
+    fn update_tip(&self) {
+        let tip = self.chain.lock().unwrap().last().unwrap().hash();
+        self.tip.send_replace(tip);
+    }
 
-    pub fn is_chain_valid(&self) -> bool {
+    fn ready_transactions(&self) -> Vec<Transaction> {
+        self.transaction_pool.ready(&self.state)
+    }
+
+    /// Next block on top of our tip, not sealed yet: a coinbase paying the block reward to our
+    /// beneficiary, then the highest priority ready transactions that fit within the chain spec's
+    /// block limits. The block is dry-run on our state as it is built, pallet hooks included, so
+    /// that only transactions that succeed after `on_initialize` go in and the header commits to
+    /// the state the block leads to.
+    pub fn block_template(&mut self) -> Block {
+        let selected = authoring::select_transactions(self.ready_transactions(), self.spec.max_block_transactions, self.spec.max_block_bytes);
         let chain = self.chain.lock().unwrap();
-        let mut previous_block = &chain[0];
-        let mut block_index = 1;
+        let height = chain.len();
+        let timestamp = Utc::now().to_rfc3339();
+        // An empty beneficiary would be paid a reward `validate_block` rejects, so it counts as none.
+        let beneficiary = self.authoring.beneficiary.clone().filter(|beneficiary| !beneficiary.is_empty());
+        let coinbase = match &beneficiary {
+            Some(beneficiary) => Transaction::coinbase(height as u64, timestamp.clone(), beneficiary.clone(), self.spec.block_reward(height)),
+            None => Transaction::coinbase(height as u64, timestamp.clone(), String::new(), Amount::ZERO),
+        };
 
-        while block_index <  chain.len() {
-            let block = &chain[block_index];
-            if block.previous_hash != previous_block.hash() {
-                return false;
+        let context = BlockContext {
+            index: height,
+            timestamp: timestamp.clone(),
+            author: beneficiary,
+        };
+        let (selected, state_root) = self.state.dry_run(|state| {
+            state.initialize_block(&context);
+            if let Err(e) = state.execute_coinbase(&coinbase) {
+                println!("Error executing coinbase of block {}: {}", context.index, e);
             }
+            // Leave out transactions that would fail, so the block executes in full.
+            let selected: Vec<Transaction> = selected.into_iter()
+                .filter(|transaction| state.execute_transaction(transaction, context.author.as_deref()).is_ok())
+                .collect();
+            state.finalize_block(&context);
+            (selected, state.state_root())
+        });
+        let mut transactions = vec![coinbase];
+        transactions.extend(selected);
+
+        let difficulty = pow::next_difficulty(&chain, &self.spec);
+        let previous_hash = chain.last().unwrap().hash();
+        let mut block = Block::new(height, timestamp, 0, difficulty, previous_hash, transactions);
+        block.state_root = state_root;
+        block
+    }
 
-            let previous_proof = previous_block.proof;
-            let proof = block.proof;
-            let calc = proof.pow(2) - previous_proof.pow(2);
-            let mut hasher = sha2::Sha256::new();
-            hasher.update(calc.to_string().as_bytes());
-            let hash_result = format!("{:x}", hasher.finalize());
+    /// Builds a block from the pool, mines it and appends it to the chain. Blocks the caller for
+    /// the whole proof of work; the node itself mines through `miner` instead.
+    pub fn create_block(&mut self) -> Block {
+        let mut block = self.block_template();
+        block.proof = self.proof_of_work(&block);
+        self.append_block(block.clone()).expect("block template only holds transactions that execute");
+        block
+    }
 
+    /// Appends a sealed block on top of our tip after checking it the same way `validate_chain`
+    /// would. A block mined on a template whose parent is no longer our tip is rejected, and so is
+    /// one with an invalid transaction, leaving chain and state untouched.
+    pub fn import_block(&mut self, block: Block) -> Result<(), String> {
+        validate_block(&self.chain.lock().unwrap(), &block, &self.spec, Utc::now())?;
+        self.append_block(block)
+    }
 
-            match &hash_result[..4] {
-                "0000" => {
-                    previous_block = block;
-                    block_index += 1;
-                },
-                _ => return false,
-            }
+    fn append_block(&mut self, block: Block) -> Result<(), String> {
+        let receipts = self.state.execute_block(&block)?;
+        self.receipts.extend(receipts.iter().map(|receipt| (receipt.transaction_hash.clone(), receipt.clone())));
+        self.chain.lock().unwrap().push(block.clone());
+        if let Err(e) = self.persist_block(&block, &receipts) {
+            println!("Error persisting block: {:?}", e);
         }
-        true
+        self.transaction_pool.prune(&self.state, Utc::now());
+        self.update_tip();
+        Ok(())
+    }
+
+    pub fn get_previous_block(&self) -> Block {
+        let chain = self.chain.lock().unwrap();
+        chain.last().unwrap().clone()
+    }
+
+    /// Searches the proof that makes `block`'s header hash meet its declared difficulty.
+    pub fn proof_of_work(&self, block: &Block) -> u64 {
+        pow::proof_of_work(&block.header())
+    }
+
+
+    pub fn is_chain_valid(&self) -> bool {
+        let chain = self.chain.lock().unwrap();
+        validate_chain(&chain, &self.spec).is_ok()
     }
 
     pub fn get_chain(&self) -> Vec<Block> {
@@ -187,11 +295,18 @@ impl Blockchain {
         chain.clone()
     }
 
+    #[allow(dead_code)]
     pub fn get_block(&self, index: usize) -> Option<Block> {
         let chain = self.chain.lock().unwrap();
         chain.get(index).cloned()
     }
 
+    /// Finds the block containing `transaction_hash` and builds its inclusion proof.
+    pub fn transaction_proof(&self, transaction_hash: &str) -> Option<TransactionProof> {
+        let chain = self.chain.lock().unwrap();
+        chain.iter().find_map(|block| block.transaction_proof(transaction_hash))
+    }
+
     pub fn add_node(&mut self, address: String) {
         self.nodes.push(address);
     }
@@ -203,146 +318,521 @@ impl Blockchain {
     async fn consensus(&mut self) {
         let nodes = self.nodes.clone();
         for node in nodes {
-            let result = self.replace_chain(node.clone());
-            if result.await.is_ok() {
-                self.replace_nonce(node.clone()).await;
-                self.replace_balance(node.clone()).await;
-                println!("Chain replaced");
+            let result = self.replace_chain(node.clone()).await.map_err(|e| e.to_string());
+            match result {
+                Ok(Some(_)) => {
+                    if let Err(e) = self.persist_chain() {
+                        println!("Error persisting chain: {:?}", e);
+                    }
+                    if self.cross_check_peer_state {
+                        self.cross_check_state(&node).await;
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => println!("Error syncing with {}: {}", node, e),
             }
         }
     }
 
-    async fn replace_nonce(&mut self, node: String) {
-        let url = format!("{}/get_all_nonce", node);
-        println!("Requesting nonce from: {}", url);
+    async fn replace_chain(&mut self, node: String) -> Result<Option<Reorg>, Box<dyn std::error::Error>> {
+        let url = format!("{}/get_chain", node);
+        println!("Requesting chain from: {}", url);
 
         let client = Client::new();
-        let response = client.get(&url).send().await.unwrap();
+        let response = client.get(&url).send().await?;
 
         if response.status().is_success() {
-            let response_json: Value = response.json().await.unwrap();
-            let nonce_map = response_json["nonce"].as_object().unwrap();
-            self.system.nonce = nonce_map.iter().map(|(k, v)| (k.clone(), v.as_u64().unwrap())).collect();
+            let response_json: Value = response.json().await?;
+            let chain: Vec<Block> = serde_json::from_value(response_json["chain"].clone())?;
+            return Ok(self.adopt_chain(chain)?);
         }
+
+        Ok(None)
     }
 
-    async fn replace_balance(&mut self, node: String) {
-        let url = format!("{}/get_all_balance", node);
-        println!("Requesting balance from: {}", url);
+    /// Switches to `chain` if fork choice prefers it over ours and it passes validation.
+    ///
+    /// State is re-derived by executing the new chain while validating it, so balances and nonces
+    /// always come from the adopted blocks, never from the peer. Transactions
+    /// from our orphaned blocks that the new branch did not include go back to the pool as long as
+    /// they are still executable.
+    fn adopt_chain(&mut self, chain: Vec<Block>) -> Result<Option<Reorg>, String> {
+        let current = self.get_chain();
+        if !fork_choice::is_better_chain(&current, &chain) {
+            return Ok(None);
+        }
 
-        let client = Client::new();
-        let response = client.get(&url).send().await.unwrap();
+        let (state, receipts) = validate_chain(&chain, &self.spec)?;
+        let common_ancestor = fork_choice::common_ancestor(&current, &chain).ok_or("Chain has a different genesis block")?;
+
+        let included: BTreeSet<&String> = chain[common_ancestor + 1..].iter()
+            .flat_map(|block| &block.transactions)
+            .map(|transaction| &transaction.hash)
+            .collect();
+        let orphaned: Vec<Transaction> = current[common_ancestor + 1..].iter()
+            .flat_map(|block| block.transactions.clone())
+            .filter(|transaction| !transaction.is_coinbase() && !included.contains(&transaction.hash))
+            .collect();
+
+        let reorg = Reorg {
+            common_ancestor,
+            depth: current.len() - common_ancestor - 1,
+            applied: chain.len() - common_ancestor - 1,
+            reinjected: vec![],
+        };
+        *self.chain.lock().unwrap() = chain;
+        self.state = state;
+        self.set_receipts(receipts);
+        self.update_tip();
+        let reorg = self.reinject_transactions(orphaned, reorg);
+        self.miner_wakeup.notify_one();
+        println!(
+            "Reorg at block {}: rolled back {} blocks, applied {}, returned {} transactions to the pool",
+            reorg.common_ancestor, reorg.depth, reorg.applied, reorg.reinjected.len()
+        );
+        Ok(Some(reorg))
+    }
 
-        if response.status().is_success() {
-            let response_json: Value = response.json().await.unwrap();
-            let balance_map = response_json["balance"].as_object().unwrap();
-            self.balances.balance = balance_map.iter().map(|(k, v)| (k.clone(), v.as_u64().unwrap())).collect();
+    /// Drops pool entries the new chain made stale, then puts orphaned transactions back in the
+    /// pool if they still pass admission against the current state.
+    fn reinject_transactions(&mut self, orphaned: Vec<Transaction>, mut reorg: Reorg) -> Reorg {
+        let now = Utc::now();
+        self.transaction_pool.prune(&self.state, now);
+        for transaction in orphaned {
+            let hash = transaction.hash.clone();
+            if self.transaction_pool.add(transaction, &self.state, now).is_ok() {
+                reorg.reinjected.push(hash);
+            }
         }
+        reorg
     }
 
+    pub fn chain_spec(&self) -> &ChainSpec {
+        &self.spec
+    }
 
-    async fn replace_chain(&mut self, node: String) -> Result<bool, Box<dyn std::error::Error>> {
-        let mut longest_chain: Option<Vec<Block>> = None;
-        let mut max_length = self.chain.lock().unwrap().len();
-        let url = format!("{}/get_chain", node);
-        println!("Requesting chain from: {}", url);
+    pub fn set_cross_check_peer_state(&mut self, enabled: bool) {
+        self.cross_check_peer_state = enabled;
+    }
 
+    /// Compares our derived state with the balances and nonces a peer reports. The peer's answer
+    /// is only logged, never applied.
+    async fn cross_check_state(&self, node: &str) {
         let client = Client::new();
-        let response = client.get(&url).send().await?;
+        let balance = client.get(format!("{}/get_all_balance", node)).send().await;
+        let nonce = client.get(format!("{}/get_all_nonce", node)).send().await;
+        let (Ok(balance), Ok(nonce)) = (balance, nonce) else {
+            println!("Could not fetch state from {} for cross-checking", node);
+            return;
+        };
 
-        if response.status().is_success() {
-            let response_json: Value = response.json().await?;
-            let length = response_json["length"].as_u64().unwrap() as usize;
-            let chain: Vec<Block> = serde_json::from_value(response_json["chain"].clone())?;
+        let balances: Option<BTreeMap<String, Amount>> = balance.json::<Value>().await.ok()
+            .and_then(|json| serde_json::from_value(json["balance"].clone()).ok());
+        let nonces: Option<BTreeMap<String, u64>> = nonce.json::<Value>().await.ok()
+            .and_then(|json| serde_json::from_value(json["nonce"].clone()).ok());
+        let (Some(balances), Some(nonces)) = (balances, nonces) else {
+            println!("Invalid state response from {}", node);
+            return;
+        };
 
-            if length > max_length && self.is_chain_valid() {
-                max_length = length;
-                longest_chain = Some(chain);
-            }
+        for mismatch in self.state_mismatches(&balances, &nonces) {
+            println!("State mismatch with {}: {}", node, mismatch);
         }
+    }
 
-        if let Some(chain) = longest_chain {
-            let mut current_chain = self.chain.lock().unwrap();
-            *current_chain = chain;
-            return Ok(true);
+    fn state_mismatches(&self, balances: &BTreeMap<String, Amount>, nonces: &BTreeMap<String, u64>) -> Vec<String> {
+        let mut mismatches = vec![];
+        let addresses: BTreeSet<&String> = self.state.balances().balance.keys().chain(balances.keys()).collect();
+        for address in addresses {
+            let ours = self.state.balances().balance(address);
+            let theirs = balances.get(address).copied().unwrap_or_default();
+            if ours != theirs {
+                mismatches.push(format!("balance of {}: ours {}, theirs {}", address, ours, theirs));
+            }
         }
-
-        Ok(false)
+        let addresses: BTreeSet<&String> = self.state.system().nonce.keys().chain(nonces.keys()).collect();
+        for address in addresses {
+            let ours = self.state.system().get_nonce(address);
+            let theirs = nonces.get(address).copied().unwrap_or_default();
+            if ours != theirs {
+                mismatches.push(format!("nonce of {}: ours {}, theirs {}", address, ours, theirs));
+            }
+        }
+        mismatches
     }
 
-    pub fn balance(&self, address: &str) -> u64 {
-        self.balances.balance(address)
+    pub fn balance(&self, address: &str) -> Amount {
+        self.state.balances().balance(address)
     }
 
     pub fn get_nonce(&self, address: &str) -> u64 {
-        self.system.get_nonce(address)
+        self.state.system().get_nonce(address)
         }
 
     pub fn get_all_nonce(&self) -> BTreeMap<String, u64> {
-        self.system.nonce.clone()
+        self.state.system().nonce.to_map()
+    }
+
+    pub fn get_all_balance(&self) -> BTreeMap<String, Amount> {
+        self.state.balances().balance.to_map()
     }
 
-    pub fn get_all_balance(&self) -> BTreeMap<String, u64> {
-        self.balances.balance.clone()
+    pub fn total_issuance(&self) -> Amount {
+        *self.state.balances().total_issuance
     }
 
 
 
 
 
-    pub fn set_balance(&mut self, address: &str, amount: u64) -> Result<(), String> {
-        self.balances.set_balance(address, amount)
+    #[allow(dead_code)]
+    pub fn set_balance(&mut self, address: &str, amount: Amount) -> Result<(), String> {
+        self.state.balances_mut().set_balance(address, amount)
     }
 
 }
 
 
-#[cfg(test)]
+/// How far ahead of our clock a block timestamp may be.
+const MAX_FUTURE_BLOCK_TIME: Duration = Duration::hours(2);
+
+/// Validates any chain, ours or a peer's, without touching node state: genesis from `spec`, hash
+/// links, declared difficulty and proof of work, transaction roots and signatures, timestamps
+/// that never go backwards, and every transaction being valid when executed. Returns the state the
+/// chain leads to and the receipts of its transactions.
+pub fn validate_chain(chain: &[Block], spec: &ChainSpec) -> Result<(State, Vec<Receipt>), String> {
+    validate_blocks(chain, spec)?;
+    State::from_chain(spec, chain)
+}
+
+/// Validates `chain` like `validate_chain`, but starts executing it from `snapshot` instead of
+/// from genesis. The snapshot must be of a block in `chain` and match that block's state root;
+/// `receipts`, the receipt log by block index, provides the receipts of the blocks up to it.
+pub fn restore_chain(chain: &[Block], spec: &ChainSpec, snapshot: StoredState, receipts: &[Vec<Receipt>]) -> Result<(State, Vec<Receipt>), String> {
+    validate_blocks(chain, spec)?;
+    let block = snapshot.block_count.checked_sub(1)
+        .and_then(|height| chain.get(height))
+        .filter(|block| block.hash() == snapshot.block_hash)
+        .ok_or("Snapshot is not of a block in the chain")?;
+    let mut state = State::restore(spec, &snapshot.storage)?;
+    if state.state_root() != block.state_root {
+        return Err(format!("Snapshot does not match the state root of block {}", block.index));
+    }
+
+    let logged = receipts.get(..snapshot.block_count).ok_or("Receipt log is behind the snapshot")?;
+    if let Some((block, _)) = chain.iter().zip(logged).find(|(block, receipts)| receipts.iter().any(|receipt| receipt.block_hash != block.hash())) {
+        return Err(format!("Receipt log does not match block {}", block.index));
+    }
+    let mut receipts = logged.concat();
+    for block in &chain[snapshot.block_count..] {
+        receipts.extend(state.execute_block(block)?);
+    }
+    Ok((state, receipts))
+}
+
+/// Genesis from `spec` and every later block checked with `validate_block`, without execution.
+fn validate_blocks(chain: &[Block], spec: &ChainSpec) -> Result<(), String> {
+    let genesis = chain.first().ok_or("Chain is empty")?;
+    if genesis.hash() != genesis_block(spec).hash() || !genesis.transactions.is_empty() {
+        return Err("Invalid genesis block".to_string());
+    }
+
+    let now = Utc::now();
+    for height in 1..chain.len() {
+        validate_block(&chain[..height], &chain[height], spec, now)?;
+    }
+    Ok(())
+}
+
+/// Checks `block` as the next block after `chain`: index, parent link, declared difficulty and
+/// proof of work, transactions root, a coinbase paying no more than the block reward to a
+/// beneficiary, size limits, signatures, and a timestamp between its parent's and `now` plus `MAX_FUTURE_BLOCK_TIME`.
+pub fn validate_block(chain: &[Block], block: &Block, spec: &ChainSpec, now: DateTime<Utc>) -> Result<(), String> {
+    let previous_block = chain.last().ok_or("Block has no parent")?;
+    let height = chain.len();
+    if block.index != height {
+        return Err(format!("Block {} should have index {}", block.index, height));
+    }
+    if block.previous_hash != previous_block.hash() {
+        return Err(format!("Block {} does not link to its parent", block.index));
+    }
+
+    if block.difficulty != pow::next_difficulty(chain, spec) {
+        return Err(format!("Block {} declares the wrong difficulty", block.index));
+    }
+
+    if !pow::valid_seal(&block.header()) {
+        return Err(format!("Block {} has an invalid proof of work", block.index));
+    }
+
+    if !block.has_valid_transactions_root() {
+        return Err(format!("Block {} has an invalid transactions root", block.index));
+    }
+
+    let Some((coinbase, transactions)) = block.transactions.split_first().filter(|(coinbase, _)| coinbase.is_coinbase()) else {
+        return Err(format!("Block {} has no coinbase", block.index));
+    };
+    let payout = coinbase.coinbase_payout().filter(|_| coinbase.nonce == height as u64 && coinbase.hash == coinbase.compute_hash());
+    let Some((beneficiary, reward)) = payout else {
+        return Err(format!("Block {} has an invalid coinbase", block.index));
+    };
+    if reward > spec.block_reward(height) {
+        return Err(format!("Block {} pays more than the block reward", block.index));
+    }
+    // Without a beneficiary the reward would be minted into an account nobody can spend from.
+    if beneficiary.is_empty() && !reward.is_zero() {
+        return Err(format!("Block {} pays its reward to no beneficiary", block.index));
+    }
+
+    if transactions.len() > spec.max_block_transactions {
+        return Err(format!("Block {} has too many transactions", block.index));
+    }
+    let bytes: usize = transactions.iter().map(|transaction| transaction.encoded_len()).sum();
+    if bytes > spec.max_block_bytes {
+        return Err(format!("Block {} exceeds the block size limit", block.index));
+    }
 
+    for transaction in transactions {
+        transaction.verify().map_err(|e| format!("Block {} has an invalid transaction: {}", block.index, e))?;
+    }
+
+    let time = parse_block_time(block)?;
+    if time < parse_block_time(previous_block)? {
+        return Err(format!("Block {} is older than its parent", block.index));
+    }
+    if time > now + MAX_FUTURE_BLOCK_TIME {
+        return Err(format!("Block {} is too far in the future", block.index));
+    }
+    Ok(())
+}
+
+fn parse_block_time(block: &Block) -> Result<DateTime<Utc>, String> {
+    DateTime::parse_from_rfc3339(&block.timestamp)
+        .map(|time| time.with_timezone(&Utc))
+        .map_err(|_| format!("Block {} has an invalid timestamp", block.index))
+}
+
+#[cfg(test)]
 //gere os testes para o módulo runtime
 mod tests {
-    use digest::typenum::assert_type;
     use super::*;
+    use crate::core_client::balance;
+    use crate::core_client::executive::ReceiptStatus;
+    use crate::domain::call::RuntimeCall;
+    use crate::domain::account;
+    use crate::domain::transaction::tests::dev_transfer;
+
+    /// Recipient and amount of a transfer, for tampering with it.
+    fn transfer_fields(transaction: &mut Transaction) -> (&mut String, &mut Amount) {
+        match &mut transaction.call {
+            RuntimeCall::Balances(balance::Call::Transfer { to, amount }) => (to, amount),
+            call => panic!("Not a transfer: {:?}", call),
+        }
+    }
+
+
+    /// Chain with two mined blocks: Alice pays Bob 30 and Bob pays Carol 5.
+    fn mined_blockchain() -> Blockchain {
+        let mut blockchain = Blockchain::new();
+        blockchain.submit_transaction(dev_transfer("Alice", 0, "Bob", 30, 0)).unwrap();
+        blockchain.create_block();
+        blockchain.submit_transaction(dev_transfer("Bob", 0, "Carol", 5, 0)).unwrap();
+        blockchain.create_block();
+        blockchain
+    }
 
     #[test]
     fn test_blockchain_new() {
         let blockchain = Blockchain::new();
         let chain = blockchain.chain.lock().unwrap();
         assert_eq!(chain.len(), 1);
-        assert_eq!(blockchain.transaction_pool.len(), 0);
-        assert_eq!(blockchain.balances.balance("Alice"), 100);
-        assert_eq!(blockchain.system.get_nonce("Alice"), 0);
+        assert_eq!(blockchain.transaction_pool.transactions().count(), 0);
+        assert_eq!(blockchain.state.balances().balance(&account::dev_address("Alice")), Amount(100));
+        assert_eq!(blockchain.state.system().get_nonce(&account::dev_address("Alice")), 0);
 
     }
 
     #[test]
     fn test_create_block() {
         let mut blockchain = Blockchain::new();
-        let previous_block = blockchain.get_previous_block();
-        let proof = blockchain.proof_of_work(previous_block.proof);
-        let previous_hash = previous_block.hash();
-
-        let transaction = Transaction {
-            hash: "".to_string(),
-            timestamp: Some(Utc::now().to_rfc3339()),
-            sender: "Alice".to_string(),
-            to: "Bob".to_string(),
-            amount: 50f64,
-            message: "".to_string(),
-        };
-
-        let block = blockchain.create_block(proof, previous_hash.clone());
+        let previous_hash = blockchain.get_previous_block().hash();
 
-        blockchain.add_transaction(transaction);
+        let block = blockchain.create_block();
 
-        let mut chain = blockchain.chain.lock().unwrap();
+        let chain = blockchain.chain.lock().unwrap();
 
         assert_eq!(chain.len(), 2);
-        assert_eq!(block.index, 2);
-        assert_eq!(block.proof, proof);
+        assert_eq!(block.index, 1);
+        assert!(pow::valid_seal(&block.header()));
         assert_eq!(block.previous_hash, previous_hash);
     }
 
+    #[test]
+    fn test_import_block() {
+        let mut blockchain = Blockchain::new();
+        let mut tip = blockchain.subscribe_tip();
+        let mut block = blockchain.block_template();
+        block.proof = blockchain.proof_of_work(&block);
+        blockchain.import_block(block.clone()).unwrap();
+        assert_eq!(blockchain.get_chain().len(), 2);
+        assert!(tip.has_changed().unwrap());
+        assert_eq!(*tip.borrow_and_update(), block.hash());
+
+        let mut stale = block.clone();
+        stale.transactions_root = "1".repeat(64);
+        assert_eq!(blockchain.import_block(stale).unwrap_err(), "Block 1 should have index 2");
+    }
+
+    #[test]
+    fn test_blocks_run_pallet_hooks() {
+        let mut blockchain = Blockchain::new();
+        blockchain.create_block();
+        let block = blockchain.create_block();
+        assert_eq!(*blockchain.state.system().block_number, block.index);
+
+        let state = State::from_chain(&ChainSpec::development(), &blockchain.get_chain()).unwrap().0;
+        assert_eq!(*state.system().block_number, block.index);
+    }
+
+    #[test]
+    fn test_transaction_receipt() {
+        let mut blockchain = Blockchain::new();
+        let transaction = dev_transfer("Alice", 0, "Bob", 10, 0);
+        blockchain.submit_transaction(transaction.clone()).unwrap();
+        assert_eq!(blockchain.transaction_receipt(&transaction.hash), None);
+
+        let block = blockchain.create_block();
+        let receipt = blockchain.transaction_receipt(&transaction.hash).unwrap();
+        assert_eq!(receipt.block_index, block.index);
+        assert_eq!(receipt.block_hash, block.hash());
+        assert_eq!(receipt.status, ReceiptStatus::Success);
+        assert!(blockchain.transaction_receipt(&block.transactions[0].hash).is_some());
+    }
+
+    #[test]
+    fn test_import_block_checks_state_root() {
+        let mut blockchain = Blockchain::new();
+        blockchain.submit_transaction(dev_transfer("Alice", 0, "Bob", 10, 0)).unwrap();
+        let mut block = blockchain.block_template();
+        let state_root = block.state_root.clone();
+        block.state_root = blockchain.state.state_root();
+        block.proof = blockchain.proof_of_work(&block);
+        assert_eq!(blockchain.import_block(block.clone()).unwrap_err(), "Block 1 has an invalid state root");
+        assert_eq!(blockchain.get_chain().len(), 1);
+
+        block.state_root = state_root;
+        block.proof = blockchain.proof_of_work(&block);
+        blockchain.import_block(block.clone()).unwrap();
+        assert_eq!(blockchain.state.state_root(), block.state_root);
+    }
+
+    #[test]
+    fn test_import_block_with_failing_transaction_is_aborted() {
+        let mut blockchain = Blockchain::new();
+        let alice = account::dev_address("Alice");
+        let mut block = blockchain.block_template();
+        let overspend = dev_transfer("Alice", 1, "Bob", 500, 0);
+        block.transactions.extend([dev_transfer("Alice", 0, "Bob", 10, 0), overspend.clone()]);
+        block.transactions_root = Block::compute_transactions_root(&block.transactions);
+        block.proof = blockchain.proof_of_work(&block);
+
+        let result = blockchain.import_block(block);
+        assert_eq!(result.unwrap_err(), format!("Block 1 has an invalid transaction {}: Insufficient balance", overspend.hash));
+        assert_eq!(blockchain.get_chain().len(), 1);
+        assert_eq!(blockchain.balance(&alice), Amount(100));
+        assert_eq!(blockchain.get_nonce(&alice), 0);
+    }
+
+    #[test]
+    fn test_block_template_leaves_out_failing_transactions() {
+        let mut blockchain = Blockchain::new();
+        let alice = account::dev_address("Alice");
+        blockchain.submit_transaction(dev_transfer("Alice", 0, "Bob", 60, 0)).unwrap();
+        blockchain.submit_transaction(dev_transfer("Alice", 1, "Bob", 30, 0)).unwrap();
+        // Alice spent elsewhere after the pool admitted her transactions.
+        blockchain.state.balances_mut().set_balance(&alice, Amount(70)).unwrap();
+
+        let block = blockchain.create_block();
+        assert_eq!(block.transactions.len(), 2);
+        assert_eq!(blockchain.balance(&alice), Amount(10));
+        assert!(blockchain.ready_transactions().is_empty());
+    }
+
+    #[test]
+    fn test_should_mine_follows_authoring_policy() {
+        let mut blockchain = Blockchain::new();
+        assert!(!blockchain.should_mine());
+        blockchain.submit_transaction(dev_transfer("Alice", 0, "Bob", 10, 0)).unwrap();
+        assert!(blockchain.should_mine());
+
+        blockchain.create_block();
+        blockchain.submit_transaction(dev_transfer("Alice", 1, "Bob", 10, 0)).unwrap();
+        assert!(!blockchain.should_mine());
+        assert!(blockchain.time_until_block().unwrap() > Duration::zero());
+
+        blockchain.set_authoring_policy(AuthoringPolicy { block_interval: Duration::zero(), empty_blocks: true, beneficiary: None });
+        blockchain.create_block();
+        assert!(blockchain.should_mine());
+    }
+
+    #[test]
+    fn test_block_template_respects_block_limits() {
+        let mut blockchain = Blockchain::new();
+        for nonce in 0..7 {
+            blockchain.submit_transaction(dev_transfer("Alice", nonce, "Bob", 1, 0)).unwrap();
+        }
+        let block = blockchain.block_template();
+        assert_eq!(block.transactions.len(), 6);
+        assert!(block.transactions[0].is_coinbase());
+        assert_eq!(block.transactions[1].nonce, 0);
+
+        blockchain.spec.max_block_bytes = block.transactions[1].encoded_len() * 2;
+        assert_eq!(blockchain.block_template().transactions.len(), 3);
+    }
+
+    #[test]
+    fn test_coinbase_pays_block_reward() {
+        let miner = account::dev_address("Miner");
+        let mut blockchain = Blockchain::new();
+        blockchain.set_authoring_policy(AuthoringPolicy { beneficiary: Some(miner.clone()), ..AuthoringPolicy::default() });
+        blockchain.create_block();
+        assert_eq!(blockchain.balance(&miner), Amount(50));
+        assert_eq!(*blockchain.state.balances().total_issuance, Amount(150));
+        assert!(blockchain.is_chain_valid());
+
+        let spec = ChainSpec::development();
+        let mut overpaid = blockchain.get_chain();
+        *transfer_fields(&mut overpaid[1].transactions[0]).1 = Amount(51);
+        overpaid[1].transactions[0].hash = overpaid[1].transactions[0].compute_hash();
+        overpaid[1].transactions_root = Block::compute_transactions_root(&overpaid[1].transactions);
+        overpaid[1].proof = pow::proof_of_work(&overpaid[1].header());
+        assert_eq!(validate_chain(&overpaid, &spec).unwrap_err(), "Block 1 pays more than the block reward");
+
+        let mut no_coinbase = blockchain.get_chain();
+        no_coinbase[1].transactions.clear();
+        no_coinbase[1].transactions_root = Block::compute_transactions_root(&no_coinbase[1].transactions);
+        no_coinbase[1].proof = pow::proof_of_work(&no_coinbase[1].header());
+        assert_eq!(validate_chain(&no_coinbase, &spec).unwrap_err(), "Block 1 has no coinbase");
+
+        let mut unclaimed = blockchain.get_chain();
+        *transfer_fields(&mut unclaimed[1].transactions[0]).0 = String::new();
+        unclaimed[1].transactions[0].hash = unclaimed[1].transactions[0].compute_hash();
+        unclaimed[1].transactions_root = Block::compute_transactions_root(&unclaimed[1].transactions);
+        unclaimed[1].proof = pow::proof_of_work(&unclaimed[1].header());
+        assert_eq!(validate_chain(&unclaimed, &spec).unwrap_err(), "Block 1 pays its reward to no beneficiary");
+    }
+
+    #[test]
+    fn test_empty_beneficiary_mints_nothing() {
+        let mut blockchain = Blockchain::new();
+        blockchain.set_authoring_policy(AuthoringPolicy { beneficiary: Some(String::new()), ..AuthoringPolicy::default() });
+        let mut block = blockchain.block_template();
+        assert_eq!(block.transactions[0].coinbase_payout(), Some(("", Amount::ZERO)));
+        block.proof = blockchain.proof_of_work(&block);
+        blockchain.import_block(block).unwrap();
+        assert_eq!(*blockchain.state.balances().total_issuance, Amount(100));
+    }
+
     #[test]
     fn test_get_previous_block() {
         let blockchain = Blockchain::new();
@@ -354,11 +844,10 @@ mod tests {
 
     #[test]
     fn test_proof_of_work() {
-        let blockchain = Blockchain::new();
-        let previous_block = blockchain.get_previous_block();
-        let previous_proof = previous_block.proof;
-        let proof = blockchain.proof_of_work(previous_proof);
-        assert_eq!(proof, 115558);
+        let mut blockchain = Blockchain::new();
+        let mut block = blockchain.block_template();
+        block.proof = blockchain.proof_of_work(&block);
+        assert!(pow::valid_seal(&block.header()));
     }
 
     #[test]
@@ -366,7 +855,7 @@ mod tests {
         let blockchain = Blockchain::new();
         let previous_block = blockchain.get_previous_block();
         let hash = previous_block.hash();
-        assert_eq!(hash.is_empty(), false);
+        assert!(!hash.is_empty());
         assert_eq!(hash.len(), 64);
     }
 
@@ -374,7 +863,305 @@ mod tests {
     fn test_is_chain_valid() {
         let blockchain = Blockchain::new();
         let is_valid = blockchain.is_chain_valid();
-        assert_eq!(is_valid, true);
+        assert!(is_valid);
+    }
+
+    #[test]
+    fn test_is_chain_valid_detects_tampered_transactions() {
+        let mut blockchain = Blockchain::new();
+        blockchain.submit_transaction(dev_transfer("Alice", 0, "Bob", 10, 0)).unwrap();
+        blockchain.create_block();
+        assert!(blockchain.is_chain_valid());
+
+        *transfer_fields(&mut blockchain.chain.lock().unwrap()[1].transactions[1]).0 = account::dev_address("Mallory");
+        assert!(!blockchain.is_chain_valid());
+    }
+
+    #[test]
+    fn test_transaction_proof() {
+        let mut blockchain = Blockchain::new();
+        let transaction = dev_transfer("Alice", 0, "Bob", 10, 0);
+        blockchain.submit_transaction(transaction.clone()).unwrap();
+        blockchain.submit_transaction(dev_transfer("Alice", 1, "Bob", 10, 0)).unwrap();
+        let block = blockchain.create_block();
+
+        let proof = blockchain.transaction_proof(&transaction.hash).unwrap();
+        assert_eq!(proof.block_hash, block.hash());
+        assert!(proof.verify());
+        assert!(blockchain.transaction_proof("unknown").is_none());
+    }
+
+    #[test]
+    fn test_nodes_agree_on_state_with_hooks_at_genesis() {
+        use crate::core_client::executive::tests::EXTEND_RUNTIME;
+        use crate::core_client::pallet::tests::Counter;
+        EXTEND_RUNTIME.set(Some(|runtime| runtime.pallet(Counter::default())));
+
+        let dir = crate::core_client::storage::tests::temp_dir("runtime-hooks");
+        let spec = ChainSpec::development();
+        let genesis = genesis_block(&spec);
+        let fresh = Blockchain::from_spec(spec.clone());
+        assert_eq!(fresh.state.state_root(), genesis.state_root);
+        assert_eq!(*fresh.state.pallet::<Counter>().unwrap().blocks, 1);
+
+        let mut blockchain = Blockchain::open(&dir, spec.clone()).unwrap();
+        assert_eq!(blockchain.state.state_root(), genesis.state_root);
+        blockchain.submit_transaction(dev_transfer("Alice", 0, "Bob", 10, 0)).unwrap();
+        let block = blockchain.create_block();
+        assert_eq!(blockchain.state.state_root(), block.state_root);
+
+        let (state, _) = validate_chain(&blockchain.get_chain(), &spec).unwrap();
+        assert_eq!(state.state_root(), block.state_root);
+        let reopened = Blockchain::open(&dir, spec).unwrap();
+        assert_eq!(reopened.state.state_root(), block.state_root);
+        assert_eq!(*reopened.state.pallet::<Counter>().unwrap().blocks, 2);
+        EXTEND_RUNTIME.set(None);
+    }
+
+    #[test]
+    fn test_open_reloads_chain_and_state() {
+        let dir = crate::core_client::storage::tests::temp_dir("runtime-open");
+        let alice = account::dev_address("Alice");
+        let transaction = dev_transfer("Alice", 0, "Bob", 10, 0);
+        let genesis_hash = {
+            let mut blockchain = Blockchain::open(&dir, ChainSpec::development()).unwrap();
+            blockchain.submit_transaction(transaction.clone()).unwrap();
+            blockchain.create_block();
+            blockchain.get_chain()[0].hash()
+        };
+
+        let blockchain = Blockchain::open(&dir, ChainSpec::development()).unwrap();
+        let chain = blockchain.get_chain();
+        assert_eq!(chain.len(), 2);
+        assert_eq!(chain[0].hash(), genesis_hash);
+        assert_eq!(blockchain.balance(&alice), Amount(90));
+        assert_eq!(blockchain.balance(&account::dev_address("Bob")), Amount(10));
+        assert_eq!(blockchain.get_nonce(&alice), 1);
+        assert_eq!(blockchain.total_issuance(), Amount(100));
+        assert_eq!(blockchain.transaction_receipt(&transaction.hash).unwrap().block_index, 1);
+        assert_eq!(Storage::open(&dir).unwrap().load_receipts().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_open_replays_block_without_state() {
+        let dir = crate::core_client::storage::tests::temp_dir("runtime-crash");
+        let transaction = dev_transfer("Alice", 0, "Bob", 10, 0);
+        {
+            let mut blockchain = Blockchain::new();
+            let snapshot = blockchain.stored_state();
+            blockchain.submit_transaction(transaction.clone()).unwrap();
+            blockchain.create_block();
+
+            // Crash right after the block was appended, before its receipts and state were saved.
+            let storage = Storage::open(&dir).unwrap();
+            storage.replace_blocks(&blockchain.get_chain()).unwrap();
+            storage.replace_receipts(&[vec![]]).unwrap();
+            storage.save_state(&snapshot).unwrap();
+        }
+
+        let blockchain = Blockchain::open(&dir, ChainSpec::development()).unwrap();
+        assert_eq!(blockchain.get_chain().len(), 2);
+        assert_eq!(blockchain.balance(&account::dev_address("Alice")), Amount(90));
+        assert_eq!(blockchain.get_nonce(&account::dev_address("Alice")), 1);
+        assert_eq!(blockchain.transaction_receipt(&transaction.hash).unwrap().block_index, 1);
+        assert_eq!(Storage::open(&dir).unwrap().load_receipts().unwrap(), blockchain.receipts_by_block());
+    }
+
+    #[test]
+    fn test_restore_chain_checks_snapshot() {
+        let blockchain = mined_blockchain();
+        let chain = blockchain.get_chain();
+        let spec = ChainSpec::development();
+        let snapshot = blockchain.stored_state();
+        let logged = blockchain.receipts_by_block();
+        let (state, receipts) = restore_chain(&chain, &spec, snapshot.clone(), &logged).unwrap();
+        assert_eq!(state.state_root(), chain[2].state_root);
+        assert_eq!(receipts, logged.concat());
+
+        let mut forged = snapshot.clone();
+        forged.storage.get_mut("Balances").unwrap()[0].1 = "1000".to_string();
+        assert_eq!(restore_chain(&chain, &spec, forged, &logged).unwrap_err(), "Snapshot does not match the state root of block 2");
+
+        let mut stale = snapshot.clone();
+        stale.block_count = 1;
+        assert_eq!(restore_chain(&chain, &spec, stale, &logged).unwrap_err(), "Snapshot is not of a block in the chain");
+
+        assert_eq!(restore_chain(&chain, &spec, snapshot.clone(), &logged[..2]).unwrap_err(), "Receipt log is behind the snapshot");
+        let mut misplaced = logged.clone();
+        misplaced.swap(1, 2);
+        assert_eq!(restore_chain(&chain, &spec, snapshot, &misplaced).unwrap_err(), "Receipt log does not match block 1");
+
+        // A snapshot taken earlier only needs the later blocks executed.
+        let mut earlier = Blockchain::new();
+        earlier.import_block(chain[1].clone()).unwrap();
+        let (state, receipts) = restore_chain(&chain, &spec, earlier.stored_state(), &logged[..2]).unwrap();
+        assert_eq!(state.state_root(), chain[2].state_root);
+        assert_eq!(state.balances().balance(&account::dev_address("Carol")), Amount(5));
+        assert_eq!(receipts, logged.concat());
+    }
+
+    #[test]
+    fn test_open_rejects_tampered_block_log() {
+        let dir = crate::core_client::storage::tests::temp_dir("runtime-tampered");
+        let mut chain = mined_blockchain().get_chain();
+        *transfer_fields(&mut chain[1].transactions[1]).0 = account::dev_address("Mallory");
+        Storage::open(&dir).unwrap().replace_blocks(&chain).unwrap();
+
+        assert!(Blockchain::open(&dir, ChainSpec::development()).is_err());
+    }
+
+    #[test]
+    fn test_validate_chain() {
+        let blockchain = mined_blockchain();
+        let chain = blockchain.get_chain();
+        assert!(validate_chain(&chain, &ChainSpec::development()).is_ok());
+        assert!(validate_chain(&[], &ChainSpec::development()).is_err());
+    }
+
+    #[test]
+    fn test_validate_chain_rejects_invalid_blocks() {
+        let chain = mined_blockchain().get_chain();
+
+        let mut broken_link = chain.clone();
+        broken_link[1].previous_hash = "0".repeat(64);
+        assert_eq!(validate_chain(&broken_link, &ChainSpec::development()).unwrap_err(), "Block 1 does not link to its parent");
+
+        let mut renumbered = chain.clone();
+        renumbered[1].index = 5;
+        renumbered[1].proof = pow::proof_of_work(&renumbered[1].header());
+        assert_eq!(validate_chain(&renumbered, &ChainSpec::development()).unwrap_err(), "Block 5 should have index 1");
+
+        let mut easier = chain.clone();
+        easier[1].difficulty = 1;
+        assert_eq!(validate_chain(&easier, &ChainSpec::development()).unwrap_err(), "Block 1 declares the wrong difficulty");
+
+        let mut bad_proof = chain.clone();
+        while pow::valid_seal(&bad_proof[2].header()) {
+            bad_proof[2].proof += 1;
+        }
+        assert_eq!(validate_chain(&bad_proof, &ChainSpec::development()).unwrap_err(), "Block 2 has an invalid proof of work");
+
+        let mut bad_root = chain.clone();
+        bad_root[2].transactions.clear();
+        assert_eq!(validate_chain(&bad_root, &ChainSpec::development()).unwrap_err(), "Block 2 has an invalid transactions root");
+
+        let mut old_block = chain.clone();
+        old_block[2].timestamp = "2000-01-01T00:00:00+00:00".to_string();
+        old_block[2].proof = pow::proof_of_work(&old_block[2].header());
+        assert_eq!(validate_chain(&old_block, &ChainSpec::development()).unwrap_err(), "Block 2 is older than its parent");
+
+        let mut future_block = chain.clone();
+        future_block[2].timestamp = (Utc::now() + Duration::days(1)).to_rfc3339();
+        future_block[2].proof = pow::proof_of_work(&future_block[2].header());
+        assert_eq!(validate_chain(&future_block, &ChainSpec::development()).unwrap_err(), "Block 2 is too far in the future");
+
+        let mut blockchain = Blockchain::new();
+        for nonce in 0..5 {
+            blockchain.submit_transaction(dev_transfer("Alice", nonce, "Bob", 1, 0)).unwrap();
+        }
+        let mut oversized = blockchain.block_template();
+        oversized.transactions.push(dev_transfer("Alice", 5, "Bob", 1, 0));
+        oversized.transactions_root = Block::compute_transactions_root(&oversized.transactions);
+        oversized.proof = pow::proof_of_work(&oversized.header());
+        let oversized = vec![blockchain.get_previous_block(), oversized];
+        assert_eq!(validate_chain(&oversized, &ChainSpec::development()).unwrap_err(), "Block 1 has too many transactions");
+
+        let mut bad_genesis = chain.clone();
+        bad_genesis[0].previous_hash = "1".to_string();
+        assert_eq!(validate_chain(&bad_genesis, &ChainSpec::development()).unwrap_err(), "Invalid genesis block");
+    }
+
+    #[test]
+    fn test_state_is_derived_from_blocks() {
+        let blockchain = mined_blockchain();
+        let state = State::from_chain(&ChainSpec::development(), &blockchain.get_chain()).unwrap().0;
+        assert_eq!(*state.balances().balance, blockchain.get_all_balance());
+        assert_eq!(*state.system().nonce, blockchain.get_all_nonce());
+        assert_eq!(state.balances().balance(&account::dev_address("Carol")), Amount(5));
+    }
+
+    #[test]
+    fn test_state_mismatches() {
+        let blockchain = mined_blockchain();
+        let mut balances = blockchain.get_all_balance();
+        let nonces = blockchain.get_all_nonce();
+        assert!(blockchain.state_mismatches(&balances, &nonces).is_empty());
+
+        balances.insert(account::dev_address("Mallory"), Amount(1000));
+        balances.remove(&account::dev_address("Carol"));
+        let mismatches = blockchain.state_mismatches(&balances, &BTreeMap::new());
+        assert_eq!(mismatches.len(), 4);
+        assert!(mismatches.contains(&format!("balance of {}: ours 0, theirs 1000", account::dev_address("Mallory"))));
+        assert!(mismatches.contains(&format!("nonce of {}: ours 1, theirs 0", account::dev_address("Alice"))));
+    }
+
+    #[test]
+    fn test_adopt_chain_derives_state() {
+        let peer = mined_blockchain();
+        let mut blockchain = Blockchain::new();
+        blockchain.set_balance(&account::dev_address("Mallory"), Amount(1000)).unwrap();
+
+        assert!(blockchain.adopt_chain(peer.get_chain()).unwrap().is_some());
+        assert_eq!(blockchain.get_chain().len(), 3);
+        assert_eq!(blockchain.get_all_balance(), peer.get_all_balance());
+        assert_eq!(blockchain.get_all_nonce(), peer.get_all_nonce());
+        assert_eq!(blockchain.balance(&account::dev_address("Mallory")), Amount(0));
+    }
+
+    #[test]
+    fn test_adopt_chain_reorg_reinjects_orphaned_transactions() {
+        let payment = dev_transfer("Alice", 0, "Bob", 30, 0);
+        let mut blockchain = Blockchain::new();
+        blockchain.submit_transaction(payment.clone()).unwrap();
+        blockchain.create_block();
+
+        let mut peer = Blockchain::new();
+        peer.create_block();
+        peer.create_block();
+
+        let reorg = blockchain.adopt_chain(peer.get_chain()).unwrap().unwrap();
+        assert_eq!(reorg, Reorg { common_ancestor: 0, depth: 1, applied: 2, reinjected: vec![payment.hash.clone()] });
+        assert_eq!(blockchain.get_chain().len(), 3);
+        assert_eq!(blockchain.balance(&account::dev_address("Alice")), Amount(100));
+        assert_eq!(blockchain.get_nonce(&account::dev_address("Alice")), 0);
+        assert_eq!(blockchain.ready_transactions().len(), 1);
+    }
+
+    #[test]
+    fn test_adopt_chain_reorg_drops_conflicting_transactions() {
+        let mut blockchain = Blockchain::new();
+        blockchain.submit_transaction(dev_transfer("Alice", 0, "Bob", 30, 0)).unwrap();
+        blockchain.create_block();
+
+        let mut peer = Blockchain::new();
+        peer.submit_transaction(dev_transfer("Alice", 0, "Carol", 10, 0)).unwrap();
+        peer.create_block();
+        peer.create_block();
+
+        let reorg = blockchain.adopt_chain(peer.get_chain()).unwrap().unwrap();
+        assert!(reorg.reinjected.is_empty());
+        assert!(blockchain.transaction_pool.transactions().next().is_none());
+        assert_eq!(blockchain.balance(&account::dev_address("Bob")), Amount(0));
+        assert_eq!(blockchain.balance(&account::dev_address("Carol")), Amount(10));
+    }
+
+    #[test]
+    fn test_adopt_chain_rejects_invalid_peer_chain() {
+        let mut chain = mined_blockchain().get_chain();
+        *transfer_fields(&mut chain[1].transactions[1]).0 = account::dev_address("Mallory");
+        let mut blockchain = Blockchain::new();
+
+        assert!(blockchain.adopt_chain(chain).is_err());
+        assert_eq!(blockchain.get_chain().len(), 1);
+        assert_eq!(blockchain.balance(&account::dev_address("Alice")), Amount(100));
+    }
+
+    #[test]
+    fn test_adopt_chain_ignores_shorter_chain() {
+        let mut blockchain = mined_blockchain();
+        let shorter = Blockchain::new().get_chain();
+        assert!(blockchain.adopt_chain(shorter).unwrap().is_none());
+        assert_eq!(blockchain.get_chain().len(), 3);
     }
 
     #[test]
@@ -388,132 +1175,178 @@ mod tests {
     fn test_get_block() {
         let blockchain = Blockchain::new();
         let block = blockchain.get_block(0);
-        assert_eq!(block.is_some(), true);
+        assert!(block.is_some());
     }
 
     #[test]
     fn test_execute_transaction() {
         let mut blockchain = Blockchain::new();
-        blockchain.balances.set_balance("Alice", 100);
-        blockchain.balances.set_balance("Bob", 100);
-        let transaction = Transaction {
-            hash: "".to_string(),
-            timestamp: Some(Utc::now().to_rfc3339()),
-            sender: "Alice".to_string(),
-            to: "Bob".to_string(),
-            amount: 50f64,
-            message: "".to_string(),
-        };
-        let result = blockchain.execute_transaction(transaction);
-        assert_eq!(result.is_ok(), true);
-        assert_eq!(blockchain.balances.balance("Alice"), 50);
-        assert_eq!(blockchain.balances.balance("Bob"), 150);
-        assert_eq!(blockchain.system.get_nonce("Alice"), 1);
+        let alice = account::dev_address("Alice");
+        let bob = account::dev_address("Bob");
+        blockchain.state.balances_mut().set_balance(&alice, Amount(100)).unwrap();
+        blockchain.state.balances_mut().set_balance(&bob, Amount(100)).unwrap();
+        let transaction = dev_transfer("Alice", 0, "Bob", 50, 0);
+        let result = blockchain.state.execute_transaction(&transaction, None);
+        assert!(result.is_ok());
+        assert_eq!(blockchain.state.balances().balance(&alice), Amount(50));
+        assert_eq!(blockchain.state.balances().balance(&bob), Amount(150));
+        assert_eq!(blockchain.state.system().get_nonce(&alice), 1);
     }
 
     #[test]
     fn test_execute_transactions() {
         let mut blockchain = Blockchain::new();
-        blockchain.balances.set_balance("Alice", 100);
-        blockchain.balances.set_balance("Bob", 100);
-        let transaction1 = Transaction {
-            hash: "".to_string(),
-            timestamp: Some(Utc::now().to_rfc3339()),
-            sender: "Alice".to_string(),
-            to: "Bob".to_string(),
-            amount: 50f64,
-            message: "".to_string(),
-        };
-        let transaction2 = Transaction {
-            hash: "".to_string(),
-            timestamp: Some(Utc::now().to_rfc3339()),
-            sender: "Bob".to_string(),
-            to: "Alice".to_string(),
-            amount: 25f64,
-            message: "".to_string(),
-        };
-        let transaction3 = Transaction {
-            hash: "".to_string(),
-            timestamp: Some(Utc::now().to_rfc3339()),
-            sender: "Bob".to_string(),
-            to: "Alice".to_string(),
-            amount: 25f64,
-            message: "".to_string(),
-        };
-        let transactions = vec![transaction1, transaction2, transaction3];
-        let result = blockchain.execute_transactions(transactions);
-        assert_eq!(result.is_ok(), true);
-        assert_eq!(blockchain.balances.balance("Alice"), 100);
-        assert_eq!(blockchain.balances.balance("Bob"), 100);
-        assert_eq!(blockchain.system.get_nonce("Alice"), 1);
-        assert_eq!(blockchain.system.get_nonce("Bob"), 2);
+        let alice = account::dev_address("Alice");
+        let bob = account::dev_address("Bob");
+        blockchain.state.balances_mut().set_balance(&alice, Amount(100)).unwrap();
+        blockchain.state.balances_mut().set_balance(&bob, Amount(100)).unwrap();
+        let transaction1 = dev_transfer("Alice", 0, "Bob", 50, 0);
+        let transaction2 = dev_transfer("Bob", 0, "Alice", 25, 0);
+        let transaction3 = dev_transfer("Bob", 1, "Alice", 25, 0);
+        let mut block = Block::new(1, Utc::now().to_rfc3339(), 0, 16, "0".to_string(), vec![transaction1, transaction2, transaction3]);
+        block.state_root = blockchain.state.state_root_after(&block).unwrap();
+        blockchain.state.execute_block(&block).unwrap();
+        assert_eq!(blockchain.state.balances().balance(&alice), Amount(100));
+        assert_eq!(blockchain.state.balances().balance(&bob), Amount(100));
+        assert_eq!(blockchain.state.system().get_nonce(&alice), 1);
+        assert_eq!(blockchain.state.system().get_nonce(&bob), 2);
     }
 
     #[tokio::test]
     async fn test_add_transaction() {
         let mut blockchain = Blockchain::new();
-        let sender = "Alice".to_string();
-        let to = "Bob".to_string();
-        let amount = 50f64;
+        let amount = 10;
 
+        for nonce in 0..4 {
+            blockchain.add_transaction(dev_transfer("Alice", nonce, "Bob", amount, 0)).await.unwrap();
+        }
 
-        let transaction = Transaction {
-            hash: "".to_string(),
-            timestamp: Some(Utc::now().to_rfc3339()),
-            sender: sender.clone(),
-            to: to.clone(),
-            amount: amount,
-            message: "".to_string(),
-        };
+        assert_eq!(blockchain.transaction_pool.transactions().count(), 4);
+        blockchain.add_transaction(dev_transfer("Alice", 4, "Bob", amount, 0)).await.unwrap();
+        assert!(blockchain.should_mine());
+        assert_eq!(blockchain.get_chain().len(), 1);
 
-        for _ in 0..4 {
-            blockchain.add_transaction(transaction.clone()).await;
-        }
+        let pooled = blockchain.ready_transactions();
+        assert_eq!(pooled[0].sender, account::dev_address("Alice"));
+        assert_eq!(pooled[0].call, RuntimeCall::Balances(balance::Call::Transfer { to: account::dev_address("Bob"), amount: Amount(amount) }));
+        assert!(!pooled[0].hash.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_add_transaction_forged_sender() {
+        let mut blockchain = Blockchain::new();
+        let mut transaction = dev_transfer("Mallory", 0, "Mallory", 100, 0);
+        transaction.sender = account::dev_address("Alice");
+        transaction.hash = transaction.compute_hash();
 
-        let transaction_pool = blockchain.transaction_pool.clone();
-        assert_eq!(transaction_pool.len(), 4);
-        blockchain.add_transaction(transaction.clone()).await;
-        let transaction_pool = blockchain.transaction_pool.clone();
-        assert_eq!(transaction_pool.len(), 0);
-        blockchain.add_transaction(transaction.clone()).await;
-        let transaction_pool = blockchain.transaction_pool.clone();
-        assert_eq!(transaction_pool[0].sender, sender);
-        assert_eq!(transaction_pool[0].to, to);
-        assert_eq!(transaction_pool[0].amount, amount);
-        assert_eq!(transaction_pool[0].hash.is_empty(), false);
+        let result = blockchain.add_transaction(transaction).await;
+        assert!(result.is_err());
+        assert_eq!(blockchain.transaction_pool.transactions().count(), 0);
     }
 
     #[test]
     fn test_execute_transaction_fail()
     {
         let mut blockchain = Blockchain::new();
-        blockchain.balances.set_balance("Alice", 100);
-        blockchain.balances.set_balance("Bob", 100);
-        let transaction = Transaction {
-            hash: "".to_string(),
-            timestamp: Some(Utc::now().to_rfc3339()),
-            sender: "Alice".to_string(),
-            to: "Bob".to_string(),
-            amount: 150f64,
-            message: "".to_string(),
-        };
-        let result = blockchain.execute_transaction(transaction);
-        assert_eq!(result.is_err(), true);
-        assert_eq!(blockchain.balances.balance("Alice"), 100);
-        assert_eq!(blockchain.balances.balance("Bob"), 100);
-        assert_eq!(blockchain.system.get_nonce("Alice"), 1);
-        assert_eq!(blockchain.system.get_nonce("Bob"), 0);
+        let alice = account::dev_address("Alice");
+        let bob = account::dev_address("Bob");
+        blockchain.state.balances_mut().set_balance(&alice, Amount(100)).unwrap();
+        blockchain.state.balances_mut().set_balance(&bob, Amount(100)).unwrap();
+        let transaction = dev_transfer("Alice", 0, "Bob", 150, 0);
+        let result = blockchain.state.execute_transaction(&transaction, None);
+        assert!(result.is_err());
+        assert_eq!(blockchain.state.balances().balance(&alice), Amount(100));
+        assert_eq!(blockchain.state.balances().balance(&bob), Amount(100));
+        assert_eq!(blockchain.state.system().get_nonce(&alice), 0);
+        assert_eq!(blockchain.state.system().get_nonce(&bob), 0);
+    }
+
+    #[test]
+    fn test_execute_transaction_invalid_signature() {
+        let mut blockchain = Blockchain::new();
+        let alice = account::dev_address("Alice");
+        let mut transaction = dev_transfer("Alice", 0, "Bob", 50, 0);
+        transaction.signature = account::sign(&account::dev_key("Mallory"), &transaction.payload());
+        let result = blockchain.state.execute_transaction(&transaction, None);
+        assert_eq!(result.unwrap_err(), "Invalid signature");
+        assert_eq!(blockchain.state.balances().balance(&alice), Amount(100));
+        assert_eq!(blockchain.state.system().get_nonce(&alice), 0);
+    }
+
+    #[test]
+    fn test_execute_transaction_replay() {
+        let mut blockchain = Blockchain::new();
+        let alice = account::dev_address("Alice");
+        let transaction = dev_transfer("Alice", 0, "Bob", 10, 0);
+        assert!(blockchain.state.execute_transaction(&transaction, None).is_ok());
+        let result = blockchain.state.execute_transaction(&transaction, None);
+        assert_eq!(result.unwrap_err(), "Stale nonce");
+        assert_eq!(blockchain.state.balances().balance(&alice), Amount(90));
+        assert_eq!(blockchain.state.system().get_nonce(&alice), 1);
+    }
+
+    #[test]
+    fn test_execute_transaction_future_nonce() {
+        let mut blockchain = Blockchain::new();
+        let alice = account::dev_address("Alice");
+        let result = blockchain.state.execute_transaction(&dev_transfer("Alice", 1, "Bob", 10, 0), None);
+        assert_eq!(result.unwrap_err(), "Future nonce");
+        assert_eq!(blockchain.state.balances().balance(&alice), Amount(100));
+        assert_eq!(blockchain.state.system().get_nonce(&alice), 0);
+    }
+
+    #[tokio::test]
+    async fn test_add_transaction_below_min_relay_fee() {
+        let mut blockchain = Blockchain::new();
+        blockchain.set_mempool_config(MempoolConfig { min_relay_fee_per_kb: Amount(10), ..MempoolConfig::default() });
+        let result = blockchain.add_transaction(dev_transfer("Alice", 0, "Bob", 10, 0)).await;
+        assert_eq!(result.unwrap_err(), "Fee below minimum relay fee");
+
+        let paying = dev_transfer("Alice", 0, "Bob", 10, 10);
+        blockchain.add_transaction(paying).await.unwrap();
+        assert_eq!(blockchain.transaction_pool.transactions().count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_add_transaction_stale_nonce() {
+        let mut blockchain = Blockchain::new();
+        blockchain.state.system_mut().increment_nonce(&account::dev_address("Alice")).unwrap();
+        let result = blockchain.add_transaction(dev_transfer("Alice", 0, "Bob", 10, 0)).await;
+        assert_eq!(result.unwrap_err(), "Stale nonce");
+        assert!(blockchain.transaction_pool.transactions().next().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_future_nonce_waits_for_gap() {
+        let mut blockchain = Blockchain::new();
+        let alice = account::dev_address("Alice");
+        blockchain.add_transaction(dev_transfer("Alice", 1, "Bob", 10, 0)).await.unwrap();
+        assert!(blockchain.ready_transactions().is_empty());
+
+        blockchain.create_block();
+        assert_eq!(blockchain.transaction_pool.transactions().count(), 1);
+        assert_eq!(blockchain.get_nonce(&alice), 0);
+
+        blockchain.add_transaction(dev_transfer("Alice", 0, "Bob", 10, 0)).await.unwrap();
+        assert_eq!(blockchain.ready_transactions().len(), 2);
+
+        let block = blockchain.create_block();
+        assert_eq!(block.transactions.len(), 3);
+        assert!(blockchain.transaction_pool.transactions().next().is_none());
+        assert_eq!(blockchain.get_nonce(&alice), 2);
+        assert_eq!(blockchain.balance(&alice), Amount(80));
     }
 
     #[test]
     fn test_balance() {
         let mut blockchain = Blockchain::new();
-        let balance = blockchain.balance("Alice");
-        assert_eq!(balance, 100);
+        let alice = account::dev_address("Alice");
+        let balance = blockchain.balance(&alice);
+        assert_eq!(balance, Amount(100));
 
-        blockchain.balances.set_balance("Alice", 200);
-        let balance = blockchain.balance("Alice");
-        assert_eq!(balance, 200);
+        blockchain.state.balances_mut().set_balance(&alice, Amount(200)).unwrap();
+        let balance = blockchain.balance(&alice);
+        assert_eq!(balance, Amount(200));
     }
 
     #[test]
@@ -522,7 +1355,7 @@ mod tests {
         let nonce = blockchain.get_nonce("Alice");
         assert_eq!(nonce, 0);
 
-        blockchain.system.increment_nonce("Alice").unwrap();
+        blockchain.state.system_mut().increment_nonce("Alice").unwrap();
         let nonce = blockchain.get_nonce("Alice");
         assert_eq!(nonce, 1);
     }
@@ -533,7 +1366,7 @@ mod tests {
         let nonce = blockchain.get_all_nonce();
         assert_eq!(nonce.len(), 0);
 
-        blockchain.system.increment_nonce("Alice").unwrap();
+        blockchain.state.system_mut().increment_nonce("Alice").unwrap();
         let nonce = blockchain.get_all_nonce();
         assert_eq!(nonce.len(), 1);
         assert_eq!(nonce["Alice"], 1);
@@ -544,10 +1377,10 @@ mod tests {
     #[test]
     fn test_set_balance() {
         let mut blockchain = Blockchain::new();
-        let result = blockchain.set_balance("Alice", 100);
-        assert_eq!(result.is_ok(), true);
+        let result = blockchain.set_balance("Alice", Amount(100));
+        assert!(result.is_ok());
         let balance = blockchain.balance("Alice");
-        assert_eq!(balance, 100);
+        assert_eq!(balance, Amount(100));
     }
 
 
@@ -560,33 +1393,8 @@ mod tests {
         let node = blockchain.get_nodes()[0].clone();
         println!("Nodes: {}", node);
         let result = blockchain.replace_chain(node.to_string()).await;
-        assert_eq!(result.is_ok(), true);
-    }
-
-    #[tokio::test]
-    async fn test_replace_nonce() {
-        let mut blockchain = Blockchain::new();
-        let node = "http://localhost:8088";
-        blockchain.add_node(node.to_string());
-        let node = blockchain.get_nodes()[0].clone();
-        println!("Nodes: {}", node);
-        blockchain.system.increment_nonce("Alice").unwrap();
-        blockchain.replace_nonce(node.to_string()).await;
-        let nonce = blockchain.system.get_nonce("Alice");
-        assert_eq!(nonce, 0);
-    }
-
-    #[tokio::test]
-    async fn test_replace_balance() {
-        let mut blockchain = Blockchain::new();
-        let node = "http://localhost:8088";
-        blockchain.add_node(node.to_string());
-        let node = blockchain.get_nodes()[0].clone();
-        println!("Nodes: {}", node);
-        blockchain.balances.set_balance("Alice", 200).expect("TODO: set balance error");
-        blockchain.replace_balance(node.to_string()).await;
-        let balance = blockchain.balance("Alice");
-        assert_eq!(balance, 100);
+        assert!(result.is_ok());
     }*/
 
+
 }
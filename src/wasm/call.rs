@@ -1,7 +1,7 @@
 use std::mem::MaybeUninit;
 
 use wasmtime::{
-    AsContext, AsContextMut, Caller, Config, Engine, Func, Instance, Memory, MemoryType, Module, OptLevel, Store,
+    AsContext, Caller, Engine, Func, Instance, Memory, MemoryType, Module, Store,
 };
 
 // Código WebAssembly em formato de texto.
@@ -80,8 +80,6 @@ mod tests {
 
     #[test]
     fn test_state_new() {
-        let engine = Engine::default();
-        let memory_type = MemoryType::new(2, Some(16));
         let store = WasmCall::new().unwrap();
         assert!(store.data().web_assembly.is_some());
     }